@@ -0,0 +1,381 @@
+use crate::document;
+use crate::parser;
+use crate::system_variables::SYSTEM_VARIABLES;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, Position, Range,
+    TextEdit,
+};
+
+/// Standard request headers offered in a request's header section
+pub const HEADER_NAMES: [&str; 30] = [
+    "Accept",
+    "Accept-Charset",
+    "Accept-Encoding",
+    "Accept-Language",
+    "Authorization",
+    "Cache-Control",
+    "Connection",
+    "Content-Disposition",
+    "Content-Encoding",
+    "Content-Language",
+    "Content-Length",
+    "Content-Type",
+    "Cookie",
+    "Date",
+    "Expect",
+    "Forwarded",
+    "From",
+    "Host",
+    "If-Match",
+    "If-Modified-Since",
+    "If-None-Match",
+    "If-Range",
+    "If-Unmodified-Since",
+    "Origin",
+    "Pragma",
+    "Range",
+    "Referer",
+    "TE",
+    "User-Agent",
+    "X-Requested-With",
+];
+
+const MIME_TYPES: [&str; 14] = [
+    "application/json",
+    "application/xml",
+    "application/x-www-form-urlencoded",
+    "application/octet-stream",
+    "application/pdf",
+    "application/graphql",
+    "multipart/form-data; boundary=",
+    "text/plain",
+    "text/html",
+    "text/csv",
+    "text/xml",
+    "image/png",
+    "image/jpeg",
+    "*/*",
+];
+
+const AUTH_SCHEMES: [&str; 5] = ["Basic", "Bearer", "Digest", "Negotiate", "AWS4-HMAC-SHA256"];
+
+const CACHE_DIRECTIVES: [&str; 10] = [
+    "no-cache",
+    "no-store",
+    "no-transform",
+    "only-if-cached",
+    "max-age=0",
+    "max-stale",
+    "min-fresh=",
+    "must-revalidate",
+    "public",
+    "private",
+];
+
+const ENCODINGS: [&str; 5] = ["gzip", "deflate", "br", "zstd", "identity"];
+
+/// What the text before the cursor is part of
+#[derive(Debug, PartialEq, Eq)]
+enum Context<'a> {
+    /// Inside `{{`; `closed` is whether `}}` already follows the cursor
+    Variable {
+        prefix: &'a str,
+        closed: bool,
+    },
+    Method {
+        prefix: &'a str,
+    },
+    HeaderName {
+        prefix: &'a str,
+    },
+    HeaderValue {
+        name: &'a str,
+        prefix: &'a str,
+    },
+    None,
+}
+
+/// Completions at `position`, given the document's file variables and the active
+/// environment's variables
+pub fn completions(
+    text: &str,
+    position: Position,
+    file_variables: &HashMap<String, String>,
+    environment_variables: &HashMap<String, String>,
+) -> Vec<CompletionItem> {
+    let context = context(text, position);
+    let replace = |prefix: &str| Range {
+        start: Position {
+            line: position.line,
+            character: position.character - prefix.encode_utf16().count() as u32,
+        },
+        end: position,
+    };
+
+    match context {
+        Context::Variable { prefix, closed } => {
+            let suffix = if closed { "" } else { "}}" };
+            let range = replace(prefix);
+
+            let mut items: Vec<CompletionItem> = file_variables
+                .iter()
+                .map(|(name, value)| (name, value, "File variable"))
+                .chain(
+                    environment_variables
+                        .iter()
+                        .filter(|(name, _)| !file_variables.contains_key(*name))
+                        .map(|(name, value)| (name, value, "Environment variable")),
+                )
+                .map(|(name, value, source)| CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some(format!("{}: {}", source, value)),
+                    text_edit: Some(edit(range, format!("{}{}", name, suffix))),
+                    ..Default::default()
+                })
+                .collect();
+            items.sort_by(|a, b| a.label.cmp(&b.label));
+
+            items.extend(SYSTEM_VARIABLES.iter().map(|(usage, description)| {
+                let name = usage.split(' ').next().unwrap_or(usage);
+                CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(usage.to_string()),
+                    documentation: Some(Documentation::String(description.to_string())),
+                    text_edit: Some(edit(range, format!("{}{}", name, suffix))),
+                    ..Default::default()
+                }
+            }));
+            items
+        }
+        Context::Method { prefix } => simple_items(
+            &parser::VALID_METHODS,
+            CompletionItemKind::KEYWORD,
+            replace(prefix),
+            " ",
+        ),
+        Context::HeaderName { prefix } => simple_items(
+            &HEADER_NAMES,
+            CompletionItemKind::FIELD,
+            replace(prefix),
+            ": ",
+        ),
+        Context::HeaderValue { name, prefix } => {
+            let values: &[&str] = match name.to_ascii_lowercase().as_str() {
+                "content-type" | "accept" => &MIME_TYPES,
+                "authorization" => &AUTH_SCHEMES,
+                "cache-control" => &CACHE_DIRECTIVES,
+                "accept-encoding" | "content-encoding" => &ENCODINGS,
+                _ => &[],
+            };
+            simple_items(values, CompletionItemKind::VALUE, replace(prefix), "")
+        }
+        Context::None => Vec::new(),
+    }
+}
+
+fn simple_items(
+    labels: &[&str],
+    kind: CompletionItemKind,
+    range: Range,
+    suffix: &str,
+) -> Vec<CompletionItem> {
+    labels
+        .iter()
+        .map(|label| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            text_edit: Some(edit(range, format!("{}{}", label, suffix))),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn edit(range: Range, new_text: String) -> CompletionTextEdit {
+    CompletionTextEdit::Edit(TextEdit { range, new_text })
+}
+
+fn context(text: &str, position: Position) -> Context<'_> {
+    let lines: Vec<&str> = text.lines().collect();
+    let cursor_line = position.line as usize;
+    let line = lines.get(cursor_line).copied().unwrap_or("");
+    let cursor = document::offset_at(line, Position::new(0, position.character));
+    let (prefix, rest) = line.split_at(cursor);
+
+    if let Some(start) = prefix.rfind("{{") {
+        let variable = &prefix[start + 2..];
+        if !variable.contains("}}") {
+            return Context::Variable {
+                prefix: variable,
+                closed: rest.starts_with("}}"),
+            };
+        }
+    }
+
+    let trimmed = prefix.trim_start();
+    if trimmed.starts_with('#') || trimmed.starts_with("//") || trimmed.starts_with('@') {
+        return Context::None;
+    }
+
+    // Find where the cursor is relative to the request line of its block
+    let block_start = lines[..cursor_line.min(lines.len())]
+        .iter()
+        .rposition(|line| line.trim().starts_with("###"))
+        .map_or(0, |idx| idx + 1);
+    let mut request_seen = false;
+    let mut in_body = false;
+    for line in lines.iter().take(cursor_line).skip(block_start) {
+        let line = line.trim();
+        if !request_seen {
+            request_seen = parser::is_request_line(line);
+        } else if line.is_empty() {
+            in_body = true;
+        }
+    }
+
+    if in_body {
+        Context::None
+    } else if !request_seen {
+        if trimmed.contains(char::is_whitespace) {
+            Context::None
+        } else {
+            Context::Method { prefix: trimmed }
+        }
+    } else if let Some((name, value)) = trimmed.split_once(':') {
+        let value = value.rsplit(',').next().unwrap_or(value);
+        Context::HeaderValue {
+            name: name.trim(),
+            prefix: value.trim_start(),
+        }
+    } else {
+        Context::HeaderName { prefix: trimmed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "@host = example.com
+GET https://{{host}}/users
+Content-Type: application/json
+Accept: text/html, app
+Cache-Control:
+Auth
+
+{\"name\": \"{{na\"}
+###
+po";
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[rstest]
+    #[case(0, 3, Context::None)]
+    #[case(1, 2, Context::Method { prefix: "GE" })]
+    #[case(1, 16, Context::Variable { prefix: "ho", closed: false })]
+    #[case(1, 18, Context::Variable { prefix: "host", closed: true })]
+    #[case(2, 4, Context::HeaderName { prefix: "Cont" })]
+    #[case(2, 20, Context::HeaderValue { name: "Content-Type", prefix: "applic" })]
+    #[case(3, 22, Context::HeaderValue { name: "Accept", prefix: "app" })]
+    #[case(4, 15, Context::HeaderValue { name: "Cache-Control", prefix: "" })]
+    #[case(5, 4, Context::HeaderName { prefix: "Auth" })]
+    #[case(7, 5, Context::None)]
+    #[case(7, 14, Context::Variable { prefix: "na", closed: false })]
+    #[case(9, 2, Context::Method { prefix: "po" })]
+    fn test_context(#[case] line: u32, #[case] character: u32, #[case] expected: Context) {
+        assert_eq!(context(DOCUMENT, Position::new(line, character)), expected);
+    }
+
+    #[test]
+    fn test_method_completions_replace_typed_prefix() {
+        let items = completions(
+            DOCUMENT,
+            Position::new(9, 2),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+
+        assert_eq!(labels(&items), parser::VALID_METHODS.to_vec());
+        assert_eq!(
+            items[1].text_edit,
+            Some(edit(
+                Range::new(Position::new(9, 0), Position::new(9, 2)),
+                "POST ".to_string()
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case(2, 20, "application/json")]
+    #[case(4, 15, "no-cache")]
+    fn test_header_value_completions(
+        #[case] line: u32,
+        #[case] character: u32,
+        #[case] expected: &str,
+    ) {
+        let items = completions(
+            DOCUMENT,
+            Position::new(line, character),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+
+        assert!(labels(&items).contains(&expected));
+    }
+
+    #[test]
+    fn test_header_name_completions_add_colon() {
+        let items = completions(
+            DOCUMENT,
+            Position::new(5, 4),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+        let authorization = items
+            .iter()
+            .find(|item| item.label == "Authorization")
+            .expect("Expected Authorization header");
+
+        assert_eq!(
+            authorization.text_edit,
+            Some(edit(
+                Range::new(Position::new(5, 0), Position::new(5, 4)),
+                "Authorization: ".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_variable_completions() {
+        let file_variables = HashMap::from([("host".to_string(), "example.com".to_string())]);
+        let environment_variables = HashMap::from([
+            ("host".to_string(), "shadowed.com".to_string()),
+            ("token".to_string(), "secret".to_string()),
+        ]);
+
+        let items = completions(
+            DOCUMENT,
+            Position::new(7, 14),
+            &file_variables,
+            &environment_variables,
+        );
+
+        assert_eq!(labels(&items)[..3], ["host", "token", "$uuid"]);
+        assert_eq!(
+            items[0].detail.as_deref(),
+            Some("File variable: example.com")
+        );
+        assert_eq!(
+            items[1].text_edit,
+            Some(edit(
+                Range::new(Position::new(7, 12), Position::new(7, 14)),
+                "token}}".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use chrono::{DateTime, NaiveDateTime};
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use url::Url;
+
+/// File in the workspace root that holds the jar when cookies are persisted
+pub const COOKIE_FILE: &str = ".http-cookies.json";
+
+/// A cookie set by a `Set-Cookie` response header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase host, without a leading dot
+    pub domain: String,
+    /// Set by a `Domain` attribute: the cookie is also sent to subdomains
+    pub include_subdomains: bool,
+    pub path: String,
+    /// Unix time in seconds; session cookies have none
+    pub expires: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received from `url` at unix time `now`. Cookies
+    /// for a domain the URL doesn't belong to are rejected.
+    pub fn parse(set_cookie: &str, url: &Url, now: i64) -> Option<Cookie> {
+        let mut attributes = set_cookie.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            include_subdomains: false,
+            path: default_path(url),
+            expires: None,
+            secure: false,
+            http_only: false,
+        };
+        let mut max_age = None;
+
+        for attribute in attributes {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if domain.is_empty() {
+                        continue;
+                    }
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.include_subdomains = true;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "expires" => cookie.expires = parse_expires(value).or(cookie.expires),
+                "max-age" => max_age = value.parse::<i64>().ok().or(max_age),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+
+        // Max-Age takes precedence over Expires
+        if let Some(seconds) = max_age {
+            cookie.expires = Some(now.saturating_add(seconds));
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether the cookie is sent with a request to `url`
+    pub fn matches(&self, url: &Url, now: i64) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_ok = if self.include_subdomains {
+            domain_matches(&host, &self.domain)
+        } else {
+            host == self.domain
+        };
+
+        domain_ok
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && !self.is_expired(now)
+    }
+
+    fn same_identity(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+/// Cookies collected from responses, sent back with later requests
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// Adds `cookie`, replacing one with the same name, domain and path. An expired cookie
+    /// only removes the one it replaces.
+    pub fn store(&mut self, cookie: Cookie, now: i64) {
+        self.cookies
+            .retain(|existing| !existing.same_identity(&cookie));
+        if !cookie.is_expired(now) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// The `Cookie` header for a request to `url`, with more specific paths first
+    pub fn header(&self, url: &Url, now: i64) -> Option<String> {
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(url, now))
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        Some(
+            cookies
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    pub fn cookies(&self) -> &[Cookie] {
+        &self.cookies
+    }
+
+    /// Removes every cookie, returning how many there were
+    pub fn clear(&mut self) -> usize {
+        std::mem::take(&mut self.cookies).len()
+    }
+
+    /// Reads a jar saved with `save`, dropping expired cookies. A missing file is an empty jar.
+    pub fn load(path: &Path, now: i64) -> Result<CookieJar, String> {
+        if !path.exists() {
+            return Ok(CookieJar::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut jar: CookieJar = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid cookie file {}: {}", path.display(), e))?;
+        jar.cookies.retain(|cookie| !cookie.is_expired(now));
+        Ok(jar)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize cookies: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// A jar shared by the server and its HTTP clients, which store cookies from every response,
+/// redirects included
+#[derive(Debug, Default)]
+pub struct SharedCookieJar(Mutex<CookieJar>);
+
+impl SharedCookieJar {
+    pub fn lock(&self) -> MutexGuard<'_, CookieJar> {
+        // A panic while holding the lock can't leave the jar half-updated
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl reqwest::cookie::CookieStore for SharedCookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let now = now();
+        let mut jar = self.lock();
+        for header in cookie_headers {
+            let Some(cookie) = header
+                .to_str()
+                .ok()
+                .and_then(|header| Cookie::parse(header, url, now))
+            else {
+                continue;
+            };
+            jar.store(cookie, now);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self.lock().header(url, now())?;
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+/// Current unix time in seconds
+pub fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// The directory of the URL's path, used when a cookie has no `Path` attribute
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(idx) => url.path()[..idx].to_string(),
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Reads an `Expires` date, in RFC 1123 or the older `Wed, 21-Oct-2015` form
+fn parse_expires(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(value)
+        .map(|date| date.timestamp())
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, "%a, %d-%b-%Y %H:%M:%S GMT")
+                .map(|date| date.and_utc().timestamp())
+        })
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).expect("Valid URL")
+    }
+
+    fn parse(set_cookie: &str, from: &str) -> Option<Cookie> {
+        Cookie::parse(set_cookie, &url(from), NOW)
+    }
+
+    fn jar(cookies: &[(&str, &str)]) -> CookieJar {
+        let mut jar = CookieJar::default();
+        for (set_cookie, from) in cookies {
+            jar.store(parse(set_cookie, from).expect("Valid cookie"), NOW);
+        }
+        jar
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let cookie = parse(
+            "session=abc; Domain=.Example.com; Path=/api; Max-Age=60; Secure; HttpOnly",
+            "https://www.example.com/login",
+        );
+
+        assert_eq!(
+            cookie,
+            Some(Cookie {
+                name: "session".to_string(),
+                value: "abc".to_string(),
+                domain: "example.com".to_string(),
+                include_subdomains: true,
+                path: "/api".to_string(),
+                expires: Some(NOW + 60),
+                secure: true,
+                http_only: true,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("a=1", "https://example.com/api/login", "/api")]
+    #[case("a=1", "https://example.com/login", "/")]
+    #[case("a=1; Path=relative", "https://example.com/api/x", "/api")]
+    fn test_parse_default_path(#[case] set_cookie: &str, #[case] from: &str, #[case] path: &str) {
+        assert_eq!(
+            parse(set_cookie, from).map(|cookie| cookie.path),
+            Some(path.to_string())
+        );
+    }
+
+    #[rstest]
+    #[case("a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", Some(1_445_412_480))]
+    #[case("a=1; expires=Wed, 21-Oct-2015 07:28:00 GMT", Some(1_445_412_480))]
+    #[case("a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=10", Some(NOW + 10))]
+    #[case("a=1; Expires=someday", None)]
+    fn test_parse_expires(#[case] set_cookie: &str, #[case] expires: Option<i64>) {
+        let cookie = parse(set_cookie, "https://example.com").expect("Valid cookie");
+
+        assert_eq!(cookie.expires, expires);
+    }
+
+    #[rstest]
+    #[case("no-equals-sign")]
+    #[case("=value")]
+    #[case("a=1; Domain=other.com")]
+    #[case("a=1; Domain=ample.com")]
+    fn test_parse_rejects(#[case] set_cookie: &str) {
+        assert_eq!(parse(set_cookie, "https://example.com"), None);
+    }
+
+    #[rstest]
+    #[case("https://example.com/", Some("host=1"))]
+    #[case("https://api.example.com/", Some("wide=2"))]
+    #[case("https://example.com/api/users", Some("deep=3; host=1"))]
+    #[case("https://example.com/apiary", Some("host=1"))]
+    #[case("https://example.org/", None)]
+    fn test_header_matches_domain_and_path(#[case] to: &str, #[case] expected: Option<&str>) {
+        let jar = jar(&[
+            ("host=1", "https://example.com/"),
+            ("wide=2; Domain=api.example.com", "https://api.example.com/"),
+            ("deep=3; Path=/api", "https://example.com/"),
+        ]);
+
+        assert_eq!(jar.header(&url(to), NOW).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_header_skips_secure_cookies_over_http() {
+        let jar = jar(&[
+            ("a=1; Secure", "https://example.com"),
+            ("b=2", "https://example.com"),
+        ]);
+
+        assert_eq!(
+            jar.header(&url("http://example.com"), NOW).as_deref(),
+            Some("b=2")
+        );
+        assert_eq!(
+            jar.header(&url("https://example.com"), NOW).as_deref(),
+            Some("a=1; b=2")
+        );
+    }
+
+    #[test]
+    fn test_store_replaces_and_expires() {
+        let mut jar = jar(&[
+            ("a=1", "https://example.com"),
+            ("b=2", "https://example.com"),
+        ]);
+
+        jar.store(
+            parse("a=3", "https://example.com").expect("Valid cookie"),
+            NOW,
+        );
+        assert_eq!(
+            jar.header(&url("https://example.com"), NOW).as_deref(),
+            Some("b=2; a=3")
+        );
+
+        jar.store(
+            parse("b=; Max-Age=0", "https://example.com").expect("Valid cookie"),
+            NOW,
+        );
+        assert_eq!(
+            jar.header(&url("https://example.com"), NOW).as_deref(),
+            Some("a=3")
+        );
+
+        jar.store(
+            parse("c=4; Max-Age=5", "https://example.com").expect("Valid cookie"),
+            NOW,
+        );
+        assert_eq!(
+            jar.header(&url("https://example.com"), NOW + 10).as_deref(),
+            Some("a=3")
+        );
+        assert_eq!(jar.clear(), 2);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("http-lsp-cookies-{}.json", std::process::id()));
+        let jar = jar(&[
+            ("session=abc; Max-Age=60", "https://example.com"),
+            ("short=1; Max-Age=5", "https://example.com"),
+        ]);
+
+        assert_eq!(CookieJar::load(&path, NOW), Ok(CookieJar::default()));
+        jar.save(&path).expect("Failed to save cookies");
+        let loaded = CookieJar::load(&path, NOW + 10).expect("Failed to load cookies");
+
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.cookies(), &jar.cookies()[..1]);
+    }
+
+    #[test]
+    fn test_shared_jar_stores_response_cookies() {
+        use reqwest::cookie::CookieStore;
+
+        let shared = SharedCookieJar::default();
+        let headers = [
+            HeaderValue::from_static("a=1; Path=/"),
+            HeaderValue::from_static("b=2; Domain=elsewhere.com"),
+        ];
+        shared.set_cookies(&mut headers.iter(), &url("https://example.com/login"));
+
+        assert_eq!(
+            shared.cookies(&url("https://example.com/profile")),
+            Some(HeaderValue::from_static("a=1"))
+        );
+    }
+}
@@ -0,0 +1,472 @@
+use crate::headers::Headers;
+use crate::multipart::{self, PartContent};
+use crate::parser::HttpRequest;
+use crate::variables;
+use base64::Engine;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Header curl adds for `--compressed`
+const COMPRESSED_ENCODINGS: &str = "gzip, deflate, br";
+
+/// curl options whose value is ignored on import, so it isn't mistaken for the URL
+const IGNORED_OPTIONS_WITH_VALUES: [&str; 21] = [
+    "-o",
+    "--output",
+    "-m",
+    "--max-time",
+    "--connect-timeout",
+    "-w",
+    "--write-out",
+    "-x",
+    "--proxy",
+    "--cacert",
+    "--cert",
+    "--key",
+    "-c",
+    "--cookie-jar",
+    "-D",
+    "--dump-header",
+    "--retry",
+    "--retry-delay",
+    "--retry-max-time",
+    "--resolve",
+    "--connect-to",
+];
+
+/// curl flags that don't change the request, dropped on import
+const IGNORED_FLAGS: [&str; 20] = [
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-L",
+    "--location",
+    "-k",
+    "--insecure",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-f",
+    "--fail",
+    "--fail-with-body",
+    "-g",
+    "--globoff",
+    "-N",
+    "--no-buffer",
+    "--no-progress-meter",
+];
+
+/// A shell command equivalent to `request`, with `variables` substituted into it.
+/// Placeholders without a value are left in place. Body files are passed to curl as they
+/// are, so `<@` files should be read into the request first to substitute their variables.
+pub fn to_curl(request: &HttpRequest, variables: &HashMap<String, String>) -> String {
+    let resolve = |text: &str| variables::substitute(text, variables);
+    // curl sends data as a POST unless told the method
+    let has_body =
+        request.body.is_some() || request.body_file.is_some() || request.multipart.is_some();
+    let request_line = match request.method.as_str() {
+        "GET" if !has_body => format!("curl {}", quote(&resolve(&request.url))),
+        "HEAD" if !has_body => format!("curl --head {}", quote(&resolve(&request.url))),
+        method => format!("curl -X {} {}", method, quote(&resolve(&request.url))),
+    };
+    let mut args = vec![request_line];
+
+    for (name, value) in &request.headers {
+        // curl chooses its own boundary for -F parts
+        if request.multipart.is_some() && name.eq_ignore_ascii_case("content-type") {
+            continue;
+        }
+        args.push(format!(
+            "-H {}",
+            quote(&format!("{}: {}", resolve(name), resolve(value)))
+        ));
+    }
+
+    if let Some(form) = &request.multipart {
+        for part in &form.parts {
+            let Some(disposition) = part.headers.get("content-disposition") else {
+                continue;
+            };
+            let Some(name) = multipart::header_param(disposition, "name") else {
+                continue;
+            };
+
+            args.push(match &part.content {
+                PartContent::Text(text) => {
+                    format!(
+                        "--form-string {}",
+                        quote(&format!("{}={}", name, resolve(text)))
+                    )
+                }
+                PartContent::File(file) => {
+                    let mut field = format!("{}=@{}", name, file.path.display());
+                    if let Some(content_type) = part.headers.get("content-type") {
+                        field.push_str(&format!(";type={}", content_type));
+                    }
+                    format!("-F {}", quote(&field))
+                }
+            });
+        }
+    } else if let Some(body_file) = &request.body_file {
+        args.push(format!(
+            "--data-binary {}",
+            quote(&format!("@{}", body_file.path.display()))
+        ));
+    } else if let Some(body) = &request.body {
+        args.push(format!("--data-raw {}", quote(&resolve(body))));
+    }
+
+    args.join(" \\\n  ")
+}
+
+/// Quotes `text` for a POSIX shell
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The lines of the `curl` command around `line`, following `\` line continuations,
+/// along with the command's text
+pub fn command_at(text: &str, line: usize) -> Option<(Range<usize>, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let continues = |idx: usize| lines[idx].trim_end().ends_with('\\');
+    lines.get(line)?;
+
+    let mut start = line;
+    while start > 0 && continues(start - 1) {
+        start -= 1;
+    }
+    let first = lines[start].trim_start();
+    if first != "curl" && !first.starts_with("curl ") {
+        return None;
+    }
+
+    let mut end = line;
+    while end + 1 < lines.len() && continues(end) {
+        end += 1;
+    }
+
+    Some((start..end + 1, lines[start..=end].join("\n")))
+}
+
+/// Converts a `curl` command into the text of a request block
+pub fn from_curl(command: &str) -> Result<String, String> {
+    let words = shell_words::split(command).map_err(|e| format!("Invalid cURL command: {}", e))?;
+    let mut words = words.into_iter();
+    if words.next().as_deref() != Some("curl") {
+        return Err("Not a cURL command".to_string());
+    }
+
+    let mut method = None;
+    let mut url = None;
+    let mut headers = Headers::new();
+    // Each value passed as data, with whether a leading `@` names a file to read it from
+    let mut data: Vec<(String, bool)> = Vec::new();
+    let mut compressed = false;
+    let mut data_in_query = false;
+
+    while let Some(word) = words.next() {
+        // Short options may carry their value directly, as in `-XPOST`
+        let (option, attached) = match word.get(..2) {
+            Some(short @ ("-X" | "-H" | "-d" | "-u" | "-A" | "-b" | "-e")) if word.len() > 2 => {
+                (short.to_string(), Some(word[2..].to_string()))
+            }
+            _ => (word, None),
+        };
+        let mut value = || {
+            attached
+                .clone()
+                .or_else(|| words.next())
+                .ok_or_else(|| format!("Missing value for {}", option))
+        };
+
+        match option.as_str() {
+            "-X" | "--request" => method = Some(value()?.to_uppercase()),
+            "-H" | "--header" => {
+                let header = value()?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid header '{}'", header))?;
+                headers.append(name.trim(), value.trim());
+            }
+            "-d" | "--data" | "--data-binary" | "--data-ascii" => data.push((value()?, true)),
+            "--data-raw" => data.push((value()?, false)),
+            "-u" | "--user" => {
+                let credentials = base64::engine::general_purpose::STANDARD.encode(value()?);
+                headers.append("Authorization", format!("Basic {}", credentials));
+            }
+            "-A" | "--user-agent" => headers.append("User-Agent", value()?),
+            "-b" | "--cookie" => headers.append("Cookie", value()?),
+            "-e" | "--referer" => headers.append("Referer", value()?),
+            "-I" | "--head" => method = Some("HEAD".to_string()),
+            "-G" | "--get" => data_in_query = true,
+            "--url" => set_url(&mut url, value()?)?,
+            "--compressed" => compressed = true,
+            "-F" | "--form" | "--form-string" => {
+                return Err("Form uploads (-F) cannot be converted yet".to_string());
+            }
+            "--data-urlencode" => {
+                return Err(
+                    "URL-encoded data (--data-urlencode) cannot be converted yet".to_string(),
+                );
+            }
+            "-T" | "--upload-file" => {
+                return Err("File uploads (-T) cannot be converted yet".to_string());
+            }
+            option if IGNORED_OPTIONS_WITH_VALUES.contains(&option) => {
+                value()?;
+            }
+            option if IGNORED_FLAGS.contains(&option) || is_ignored_flag_group(option) => {}
+            option if option.starts_with('-') && option.len() > 1 => {
+                return Err(format!("Unsupported cURL option {}", option));
+            }
+            _ => set_url(&mut url, option)?,
+        }
+    }
+
+    let mut url = url.ok_or("The cURL command has no URL")?;
+    // -G sends the data as the query string of a GET request
+    if data_in_query && !data.is_empty() {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        let query: Vec<&str> = data.iter().map(|(value, _)| value.as_str()).collect();
+        url.push_str(&query.join("&"));
+        data.clear();
+    }
+    let method = method.unwrap_or_else(|| if data.is_empty() { "GET" } else { "POST" }.to_string());
+    if !data.is_empty() && !headers.contains_key("content-type") {
+        headers.append("Content-Type", "application/x-www-form-urlencoded");
+    }
+    if compressed && !headers.contains_key("accept-encoding") {
+        headers.append("Accept-Encoding", COMPRESSED_ENCODINGS);
+    }
+
+    let mut block = format!("{} {}", method, url);
+    for (name, value) in &headers {
+        block.push_str(&format!("\n{}: {}", name, value));
+    }
+    if !data.is_empty() {
+        // `@file` reads the body from a file, like `< file` does
+        let body = match data.as_slice() {
+            [(single, true)] if single.starts_with('@') => format!("< {}", &single[1..]),
+            _ => {
+                let values: Vec<&str> = data.iter().map(|(value, _)| value.as_str()).collect();
+                values.join("&")
+            }
+        };
+        block.push_str(&format!("\n\n{}", body));
+    }
+
+    Ok(block)
+}
+
+/// Sets the request's URL, which curl commands give only once here
+fn set_url(url: &mut Option<String>, value: String) -> Result<(), String> {
+    if let Some(first) = url {
+        return Err(format!(
+            "The cURL command has more than one URL: '{}' and '{}'",
+            first, value
+        ));
+    }
+    *url = Some(value);
+    Ok(())
+}
+
+/// Whether `option` combines single-letter ignored flags, as in `-sSL`
+fn is_ignored_flag_group(option: &str) -> bool {
+    option.len() > 2
+        && !option.starts_with("--")
+        && option[1..].chars().all(|flag| {
+            IGNORED_FLAGS
+                .iter()
+                .any(|ignored| ignored.len() == 2 && ignored.ends_with(flag))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_document;
+    use rstest::rstest;
+
+    fn request(text: &str) -> HttpRequest {
+        parse_document(text)
+            .requests
+            .into_iter()
+            .next()
+            .expect("Expected a request")
+    }
+
+    #[test]
+    fn test_to_curl() {
+        let request = request(
+            "POST https://{{host}}/users\nContent-Type: application/json\n\
+             X-Note: it's {{missing}}\n\n{\"name\": \"Ada\"}",
+        );
+        let variables = HashMap::from([("host".to_string(), "example.com".to_string())]);
+
+        assert_eq!(
+            to_curl(&request, &variables),
+            "curl -X POST 'https://example.com/users' \\\n  \
+             -H 'Content-Type: application/json' \\\n  \
+             -H 'X-Note: it'\\''s {{missing}}' \\\n  \
+             --data-raw '{\"name\": \"Ada\"}'"
+        );
+    }
+
+    #[test]
+    fn test_to_curl_with_multipart_and_body_file() {
+        let upload = request(
+            "POST https://example.com/upload\nContent-Type: multipart/form-data; boundary=b\n\n\
+             --b\nContent-Disposition: form-data; name=\"title\"\n\nBeach\n\
+             --b\nContent-Disposition: form-data; name=\"photo\"; filename=\"a.png\"\n\
+             Content-Type: image/png\n\n< ./a.png\n--b--",
+        );
+        assert_eq!(
+            to_curl(&upload, &HashMap::new()),
+            "curl -X POST 'https://example.com/upload' \\\n  \
+             --form-string 'title=Beach' \\\n  \
+             -F 'photo=@./a.png;type=image/png'"
+        );
+
+        let file = request("PUT https://example.com/data\n\n< ./data.json");
+        assert_eq!(
+            to_curl(&file, &HashMap::new()),
+            "curl -X PUT 'https://example.com/data' \\\n  --data-binary '@./data.json'"
+        );
+    }
+
+    #[rstest]
+    #[case("curl https://example.com/users", "GET https://example.com/users")]
+    #[case(
+        "curl -XPUT 'https://example.com/users/1' -H 'Accept: application/json' -d '{\"a\": 1}'",
+        "PUT https://example.com/users/1\nAccept: application/json\n\
+         Content-Type: application/x-www-form-urlencoded\n\n{\"a\": 1}"
+    )]
+    #[case(
+        "curl -s -L --compressed -u ada:secret -o out.json https://example.com",
+        "GET https://example.com\nAuthorization: Basic YWRhOnNlY3JldA==\n\
+         Accept-Encoding: gzip, deflate, br"
+    )]
+    #[case(
+        "curl https://example.com --data-raw 'a=1' --data 'b=2'",
+        "POST https://example.com\nContent-Type: application/x-www-form-urlencoded\n\na=1&b=2"
+    )]
+    #[case(
+        "curl --data-binary @./body.json -H 'Content-Type: application/json' https://example.com",
+        "POST https://example.com\nContent-Type: application/json\n\n< ./body.json"
+    )]
+    #[case(
+        "curl -G https://example.com/search -d q=rust -d page=2",
+        "GET https://example.com/search?q=rust&page=2"
+    )]
+    #[case(
+        "curl --get 'https://example.com/search?lang=en' --data q=rust",
+        "GET https://example.com/search?lang=en&q=rust"
+    )]
+    #[case("curl -G https://example.com", "GET https://example.com")]
+    #[case("curl https://example.com -c cookies.txt", "GET https://example.com")]
+    #[case(
+        "curl https://example.com --cookie-jar cookies.txt",
+        "GET https://example.com"
+    )]
+    #[case("curl https://example.com --retry 3", "GET https://example.com")]
+    #[case("curl -sSL https://example.com", "GET https://example.com")]
+    #[case(
+        "curl https://example.com --data-raw @literal",
+        "POST https://example.com\nContent-Type: application/x-www-form-urlencoded\n\n@literal"
+    )]
+    #[case(
+        "curl https://example.com -d @./form.txt",
+        "POST https://example.com\nContent-Type: application/x-www-form-urlencoded\n\n< ./form.txt"
+    )]
+    #[case(
+        "curl https://example.com --resolve example.com:443:127.0.0.1",
+        "GET https://example.com"
+    )]
+    fn test_from_curl(#[case] command: &str, #[case] expected: &str) {
+        assert_eq!(from_curl(command).as_deref(), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("wget https://example.com", "Not a cURL command")]
+    #[case("curl -s", "no URL")]
+    #[case("curl -H", "Missing value for -H")]
+    #[case("curl -F 'a=@b' https://example.com", "Form uploads")]
+    #[case("curl https://example.com --data-urlencode a=b", "--data-urlencode")]
+    #[case("curl https://example.com -T ./file.txt", "File uploads (-T)")]
+    #[case(
+        "curl https://example.com --upload-file ./file.txt",
+        "File uploads (-T)"
+    )]
+    #[case("curl https://example.com --retry", "Missing value for --retry")]
+    #[case(
+        "curl https://example.com --max-redirs 3",
+        "Unsupported cURL option --max-redirs"
+    )]
+    #[case("curl -r 0-99 https://example.com", "Unsupported cURL option -r")]
+    #[case(
+        "curl --limit-rate 1k https://example.com",
+        "Unsupported cURL option --limit-rate"
+    )]
+    #[case("curl -sSx https://example.com", "Unsupported cURL option -sSx")]
+    #[case(
+        "curl https://example.com https://example.org",
+        "more than one URL: 'https://example.com' and 'https://example.org'"
+    )]
+    #[case(
+        "curl --url https://example.com https://example.org",
+        "more than one URL"
+    )]
+    #[case("curl 'https://example.com", "Invalid cURL command")]
+    fn test_from_curl_errors(#[case] command: &str, #[case] expected: &str) {
+        let error = from_curl(command).expect_err("Expected an error");
+        assert!(error.contains(expected), "unexpected error: {}", error);
+    }
+
+    #[rstest]
+    #[case("GET https://example.com/search?q=a b")]
+    #[case("POST https://example.com/users\nContent-Type: application/json\nAuthorization: Bearer x\n\n{\n  \"name\": \"O'Brien\"\n}")]
+    #[case("DELETE https://example.com/users/1\nX-Empty: ")]
+    #[case("HEAD https://example.com")]
+    #[case("GET https://example.com/search\nContent-Type: application/json\n\n{\"q\": \"rust\"}")]
+    #[case("HEAD https://example.com\nContent-Type: text/plain\n\n< ./query.txt")]
+    #[case("PATCH https://example.com\nContent-Type: text/plain\n\n< ./patch.txt")]
+    fn test_http_round_trips_through_curl(#[case] text: &str) {
+        let original = request(text);
+        let converted = request(
+            &from_curl(&to_curl(&original, &HashMap::new())).expect("Expected a request block"),
+        );
+
+        assert_eq!(converted.method, original.method);
+        assert_eq!(converted.url, original.url);
+        assert_eq!(converted.headers, original.headers);
+        assert_eq!(converted.body, original.body);
+        assert_eq!(converted.body_file, original.body_file);
+    }
+
+    #[rstest]
+    #[case("curl 'https://example.com/a'")]
+    #[case("curl -X POST 'https://example.com/a' \\\n  -H 'Content-Type: application/json' \\\n  --data-raw '{\"quote\": \"it'\\''s\"}'")]
+    #[case("curl --head 'https://example.com/a' \\\n  -H 'Accept: */*'")]
+    fn test_curl_round_trips_through_http(#[case] command: &str) {
+        let block = from_curl(command).expect("Expected a request block");
+
+        assert_eq!(to_curl(&request(&block), &HashMap::new()), command);
+    }
+
+    #[test]
+    fn test_command_at_follows_continuations() {
+        let text = "GET https://example.com\n\ncurl -X POST \\\n  -d 'a=1' \\\n  https://example.com\nnext";
+
+        assert_eq!(
+            command_at(text, 3),
+            Some((
+                2..5,
+                "curl -X POST \\\n  -d 'a=1' \\\n  https://example.com".to_string()
+            ))
+        );
+        assert_eq!(command_at(text, 0), None);
+        assert_eq!(command_at(text, 5), None);
+    }
+}
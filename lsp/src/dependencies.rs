@@ -0,0 +1,250 @@
+use crate::multipart::PartContent;
+use crate::parser::HttpRequest;
+use crate::{response_variables, variables};
+
+/// Names of the requests whose responses `request` references, in order of first use.
+/// References inside `<@` body files are only seen once the files have been read into the
+/// request.
+pub fn referenced_requests(request: &HttpRequest) -> Vec<&str> {
+    let parts = request
+        .multipart
+        .iter()
+        .flat_map(|multipart| &multipart.parts);
+    let texts = std::iter::once(request.url.as_str())
+        .chain(
+            request
+                .headers
+                .iter()
+                .flat_map(|(name, value)| [name.as_str(), value.as_str()]),
+        )
+        .chain(request.body.as_deref())
+        .chain(parts.flat_map(|part| {
+            let content = match &part.content {
+                PartContent::Text(text) => Some(text.as_str()),
+                PartContent::File(_) => None,
+            };
+            part.headers
+                .iter()
+                .flat_map(|(name, value)| [name.as_str(), value.as_str()])
+                .chain(content)
+        }));
+
+    let mut names = Vec::new();
+    for text in texts {
+        for expression in variables::placeholders(text) {
+            if let Some(reference) = response_variables::parse_reference(expression) {
+                if !names.contains(&reference.request_name) {
+                    names.push(reference.request_name);
+                }
+            }
+        }
+    }
+
+    names
+}
+
+/// Orders the named requests that must run before `target` so that every response it
+/// references, directly or through other requests, is available. Requests for which
+/// `has_response` returns true are not run again.
+pub fn plan_prerequisites<'a, F>(
+    target: &'a HttpRequest,
+    requests: &'a [HttpRequest],
+    has_response: F,
+) -> Result<Vec<&'a HttpRequest>, String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut planner = Planner {
+        requests,
+        has_response,
+        path: target.name.iter().map(String::as_str).collect(),
+        plan: Vec::new(),
+    };
+
+    for name in referenced_requests(target) {
+        planner.visit(name)?;
+    }
+
+    Ok(planner.plan)
+}
+
+struct Planner<'a, F> {
+    requests: &'a [HttpRequest],
+    has_response: F,
+    /// Names on the current dependency path, used to detect cycles
+    path: Vec<&'a str>,
+    plan: Vec<&'a HttpRequest>,
+}
+
+impl<'a, F> Planner<'a, F>
+where
+    F: Fn(&str) -> bool,
+{
+    fn visit(&mut self, name: &'a str) -> Result<(), String> {
+        let already_planned = self
+            .plan
+            .iter()
+            .any(|request| request.name.as_deref() == Some(name));
+        if already_planned || (self.has_response)(name) {
+            return Ok(());
+        }
+
+        if let Some(start) = self.path.iter().position(|n| *n == name) {
+            let mut cycle = self.path[start..].to_vec();
+            cycle.push(name);
+            return Err(format!(
+                "Circular request dependency: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        let request = self
+            .requests
+            .iter()
+            .find(|request| request.name.as_deref() == Some(name))
+            .ok_or_else(|| format!("Request '{}' is not defined in this document", name))?;
+
+        self.path.push(name);
+        for dependency in referenced_requests(request) {
+            self.visit(dependency)?;
+        }
+        self.path.pop();
+
+        self.plan.push(request);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_http_file;
+
+    fn plan_names(content: &str, target: &str, sent: &[&str]) -> Result<Vec<String>, String> {
+        let requests = parse_http_file(content);
+        let target = requests
+            .iter()
+            .find(|r| r.url.ends_with(target))
+            .expect("Target request not found");
+
+        plan_prerequisites(target, &requests, |name| sent.contains(&name)).map(|plan| {
+            plan.iter()
+                .map(|r| r.name.clone().unwrap_or_default())
+                .collect()
+        })
+    }
+
+    const CHAIN: &str = r#"# @name login
+POST https://example.com/login
+###
+# @name profile
+GET https://example.com/profile
+Authorization: Bearer {{login.response.body.$.token}}
+###
+# @name orders
+GET https://example.com/users/{{profile.response.body.$.id}}/orders
+Authorization: Bearer {{login.response.body.$.token}}
+###
+GET https://example.com/orders/{{orders.response.body.$[0].id}}"#;
+
+    #[test]
+    fn test_referenced_requests() {
+        let requests = parse_http_file(CHAIN);
+        assert!(referenced_requests(&requests[0]).is_empty());
+        assert_eq!(referenced_requests(&requests[2]), vec!["profile", "login"]);
+    }
+
+    #[test]
+    fn test_referenced_requests_in_multipart_parts() {
+        let content = r#"POST https://example.com/upload
+Content-Type: multipart/form-data; boundary=b
+
+--b
+Content-Disposition: form-data; name="{{form.response.body.$.field}}"
+
+{{login.response.body.$.token}}
+--b--"#;
+        let requests = parse_http_file(content);
+
+        assert_eq!(referenced_requests(&requests[0]), vec!["form", "login"]);
+    }
+
+    #[test]
+    fn test_plan_runs_dependencies_in_order() {
+        assert_eq!(
+            plan_names(CHAIN, "/orders/{{orders.response.body.$[0].id}}", &[]),
+            Ok(vec![
+                "login".to_string(),
+                "profile".to_string(),
+                "orders".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_plan_skips_requests_with_responses() {
+        assert_eq!(
+            plan_names(
+                CHAIN,
+                "/orders/{{orders.response.body.$[0].id}}",
+                &["login"]
+            ),
+            Ok(vec!["profile".to_string(), "orders".to_string()])
+        );
+        assert_eq!(
+            plan_names(
+                CHAIN,
+                "/orders/{{orders.response.body.$[0].id}}",
+                &["orders"]
+            ),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn test_plan_without_references_is_empty() {
+        assert_eq!(plan_names(CHAIN, "/login", &[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_plan_detects_cycles() {
+        let content = r#"# @name a
+GET https://example.com/a/{{b.response.body.$.id}}
+###
+# @name b
+GET https://example.com/b/{{c.response.body.$.id}}
+###
+# @name c
+GET https://example.com/c/{{a.response.body.$.id}}
+###
+GET https://example.com/start/{{a.response.body.$.id}}"#;
+
+        assert_eq!(
+            plan_names(content, "/start/{{a.response.body.$.id}}", &[]),
+            Err("Circular request dependency: a -> b -> c -> a".to_string())
+        );
+    }
+
+    #[test]
+    fn test_plan_detects_self_reference() {
+        let content = r#"# @name refresh
+POST https://example.com/refresh
+Authorization: Bearer {{refresh.response.body.$.token}}"#;
+
+        assert_eq!(
+            plan_names(content, "/refresh", &[]),
+            Err("Circular request dependency: refresh -> refresh".to_string())
+        );
+        assert_eq!(plan_names(content, "/refresh", &["refresh"]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_plan_reports_unknown_requests() {
+        let content = "GET https://example.com/{{missing.response.body.$.id}}";
+
+        assert_eq!(
+            plan_names(content, "/{{missing.response.body.$.id}}", &[]),
+            Err("Request 'missing' is not defined in this document".to_string())
+        );
+    }
+}
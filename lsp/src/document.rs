@@ -0,0 +1,198 @@
+use crate::parser::{self, ParsedDocument};
+use std::sync::Arc;
+use tower_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
+
+/// An open document's text, kept in sync with the editor, and its cached parse
+#[derive(Debug)]
+pub struct Document {
+    text: String,
+    version: i32,
+    /// The last parse and the version it was computed for
+    parsed: Option<(i32, Arc<ParsedDocument>)>,
+}
+
+impl Document {
+    pub fn new(text: String, version: i32) -> Self {
+        Document {
+            text,
+            version,
+            parsed: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Applies the edits of a `didChange` in order. Changes without a range replace the whole
+    /// text. Returns false, leaving the text untouched, when `version` is not newer than the
+    /// current one.
+    pub fn apply_changes(
+        &mut self,
+        changes: &[TextDocumentContentChangeEvent],
+        version: i32,
+    ) -> bool {
+        if version <= self.version {
+            return false;
+        }
+
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let start = offset_at(&self.text, range.start);
+                    let end = offset_at(&self.text, range.end).max(start);
+                    self.text.replace_range(start..end, &change.text);
+                }
+                None => self.text = change.text.clone(),
+            }
+        }
+
+        self.version = version;
+        true
+    }
+
+    /// Parsed requests for the current version, parsing only if it has not been parsed yet
+    pub fn parsed(&mut self) -> Arc<ParsedDocument> {
+        match &self.parsed {
+            Some((version, parsed)) if *version == self.version => parsed.clone(),
+            _ => {
+                let parsed = Arc::new(parser::parse_document(&self.text));
+                self.parsed = Some((self.version, parsed.clone()));
+                parsed
+            }
+        }
+    }
+}
+
+/// Byte offset of an LSP position, whose character is counted in UTF-16 code units.
+/// Positions past the end of a line or of the text are clamped.
+pub fn offset_at(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line = &text[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + idx;
+        }
+        units += c.len_utf16();
+    }
+
+    line_start + line.len()
+}
+
+/// LSP position of a byte offset, the inverse of [`offset_at`]
+pub fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    Position::new(
+        before.matches('\n').count() as u32,
+        parser::utf16_len(&before[line_start..]) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tower_lsp::lsp_types::Range;
+
+    fn change(range: Option<Range>, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Option<Range> {
+        Some(Range::new(
+            Position::new(start.0, start.1),
+            Position::new(end.0, end.1),
+        ))
+    }
+
+    #[rstest]
+    #[case("GET https://a.com", range((0, 0), (0, 3)), "POST", "POST https://a.com")]
+    #[case("GET /\nAccept: x", range((1, 8), (1, 9)), "json", "GET /\nAccept: json")]
+    #[case("GET /", range((0, 5), (0, 5)), "\nAccept: x", "GET /\nAccept: x")]
+    #[case("a\nb\nc", range((0, 1), (2, 0)), "", "ac")]
+    #[case("é😀x", range((0, 3), (0, 4)), "y", "é😀y")]
+    #[case("short", range((0, 99), (5, 0)), "!", "short!")]
+    #[case("old", None, "new", "new")]
+    fn test_apply_change(
+        #[case] text: &str,
+        #[case] range: Option<Range>,
+        #[case] new_text: &str,
+        #[case] expected: &str,
+    ) {
+        let mut document = Document::new(text.to_string(), 1);
+
+        assert!(document.apply_changes(&[change(range, new_text)], 2));
+        assert_eq!(document.text, expected);
+        assert_eq!(document.version, 2);
+    }
+
+    #[test]
+    fn test_apply_changes_in_order() {
+        let mut document = Document::new("GET /".to_string(), 1);
+        let changes = [
+            change(range((0, 0), (0, 3)), "POST"),
+            change(range((0, 5), (0, 5)), "https://a.com"),
+        ];
+
+        assert!(document.apply_changes(&changes, 2));
+        assert_eq!(document.text, "POST https://a.com/");
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    fn test_apply_changes_ignores_stale_versions(#[case] version: i32) {
+        let mut document = Document::new("GET /".to_string(), 2);
+        let parsed = document.parsed();
+
+        assert!(!document.apply_changes(&[change(None, "POST /")], version));
+        assert_eq!(document.text, "GET /");
+        assert_eq!(document.version, 2);
+        assert!(Arc::ptr_eq(&parsed, &document.parsed()));
+    }
+
+    #[test]
+    fn test_parse_is_cached_until_changed() {
+        let mut document = Document::new("GET https://a.com".to_string(), 1);
+
+        let first = document.parsed();
+        assert!(Arc::ptr_eq(&first, &document.parsed()));
+
+        document.apply_changes(&[change(range((0, 0), (0, 3)), "POST")], 2);
+        let second = document.parsed();
+
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(second.requests[0].method, "POST");
+    }
+
+    #[rstest]
+    #[case("GET /", 0, (0, 0))]
+    #[case("GET /\nAccept: x", 14, (1, 8))]
+    #[case("é😀\nx😀y", 12, (1, 3))]
+    fn test_position_at_inverts_offset_at(
+        #[case] text: &str,
+        #[case] offset: usize,
+        #[case] expected: (u32, u32),
+    ) {
+        let position = position_at(text, offset);
+
+        assert_eq!(position, Position::new(expected.0, expected.1));
+        assert_eq!(offset_at(text, position), offset);
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Environment file meant to be committed alongside the .http files
+pub const PUBLIC_ENV_FILE: &str = "http-client.env.json";
+
+/// Environment file for secrets, meant to be gitignored
+pub const PRIVATE_ENV_FILE: &str = "http-client.private.env.json";
+
+/// Environment whose variables are available in every other environment
+pub const SHARED_ENVIRONMENT: &str = "$shared";
+
+/// Named sets of variables loaded from the public and private environment files
+#[derive(Debug, Clone, Default)]
+pub struct Environments {
+    environments: BTreeMap<String, HashMap<String, String>>,
+    /// File each `(environment, variable)` value was last read from
+    files: HashMap<(String, String), &'static str>,
+}
+
+/// Where an environment variable visible in the active environment gets its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition<'a> {
+    pub environment: &'a str,
+    pub file: &'static str,
+    pub value: &'a str,
+}
+
+impl Environments {
+    /// Loads and merges the environment files in `dir`. Missing files are treated as empty.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let public = read_optional(&dir.join(PUBLIC_ENV_FILE))?;
+        let private = read_optional(&dir.join(PRIVATE_ENV_FILE))?;
+
+        Self::from_json(public.as_deref(), private.as_deref())
+    }
+
+    /// Builds environments from file contents, with private values overriding public ones
+    pub fn from_json(public: Option<&str>, private: Option<&str>) -> Result<Self> {
+        let mut environments = Environments::default();
+
+        if let Some(public) = public {
+            environments
+                .merge(public, PUBLIC_ENV_FILE)
+                .context(PUBLIC_ENV_FILE)?;
+        }
+        if let Some(private) = private {
+            environments
+                .merge(private, PRIVATE_ENV_FILE)
+                .context(PRIVATE_ENV_FILE)?;
+        }
+
+        Ok(environments)
+    }
+
+    fn merge(&mut self, json: &str, file: &'static str) -> Result<()> {
+        let root: Value = serde_json::from_str(json)?;
+        let Value::Object(root) = root else {
+            anyhow::bail!("Expected an object of environments");
+        };
+
+        for (env_name, env_value) in root {
+            let Value::Object(values) = env_value else {
+                anyhow::bail!("Environment '{}' must be an object", env_name);
+            };
+
+            let variables = self.environments.entry(env_name.clone()).or_default();
+            for (name, value) in values {
+                let value = match value {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                self.files.insert((env_name.clone(), name.clone()), file);
+                variables.insert(name, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Names of the selectable environments, excluding `$shared`
+    pub fn names(&self) -> Vec<&str> {
+        self.environments
+            .keys()
+            .map(String::as_str)
+            .filter(|name| *name != SHARED_ENVIRONMENT)
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        name != SHARED_ENVIRONMENT && self.environments.contains_key(name)
+    }
+
+    /// Variables visible in `active`: the shared ones overlaid with the active environment's
+    pub fn variables(&self, active: Option<&str>) -> HashMap<String, String> {
+        let mut variables = self
+            .environments
+            .get(SHARED_ENVIRONMENT)
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(env) = active.and_then(|name| self.environments.get(name)) {
+            variables.extend(env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+
+        variables
+    }
+
+    /// Finds the environment and file that give `name` its value in `active`
+    pub fn definition(&self, name: &str, active: Option<&str>) -> Option<Definition<'_>> {
+        active
+            .filter(|active| *active != SHARED_ENVIRONMENT)
+            .into_iter()
+            .chain([SHARED_ENVIRONMENT])
+            .find_map(|environment| {
+                let (environment, variables) = self.environments.get_key_value(environment)?;
+                let value = variables.get(name)?;
+                Some(Definition {
+                    environment,
+                    file: self.files[&(environment.clone(), name.to_string())],
+                    value,
+                })
+            })
+    }
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const PUBLIC: &str = r#"{
+        "$shared": { "version": "v1", "timeout": 30 },
+        "dev": { "baseUrl": "http://localhost:8080", "token": "" },
+        "staging": { "baseUrl": "https://staging.example.com" }
+    }"#;
+
+    const PRIVATE: &str = r#"{
+        "dev": { "token": "dev-secret" },
+        "local": { "baseUrl": "http://127.0.0.1:3000" }
+    }"#;
+
+    #[test]
+    fn test_names_exclude_shared() {
+        let envs = Environments::from_json(Some(PUBLIC), Some(PRIVATE))
+            .expect("Environments should parse");
+
+        assert_eq!(envs.names(), vec!["dev", "local", "staging"]);
+        assert!(envs.contains("dev"));
+        assert!(!envs.contains(SHARED_ENVIRONMENT));
+    }
+
+    #[rstest]
+    #[case(Some("dev"), "baseUrl", Some("http://localhost:8080"))]
+    #[case(Some("dev"), "token", Some("dev-secret"))]
+    #[case(Some("dev"), "version", Some("v1"))]
+    #[case(Some("dev"), "timeout", Some("30"))]
+    #[case(Some("staging"), "token", None)]
+    #[case(Some("local"), "baseUrl", Some("http://127.0.0.1:3000"))]
+    #[case(None, "version", Some("v1"))]
+    #[case(None, "baseUrl", None)]
+    #[case(Some("missing"), "version", Some("v1"))]
+    fn test_variables_merge_private_and_shared(
+        #[case] active: Option<&str>,
+        #[case] name: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let envs = Environments::from_json(Some(PUBLIC), Some(PRIVATE))
+            .expect("Environments should parse");
+
+        assert_eq!(
+            envs.variables(active).get(name).map(String::as_str),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_missing_files_are_empty() {
+        let envs = Environments::from_json(None, None).expect("Empty environments");
+        assert!(envs.names().is_empty());
+        assert!(envs.variables(Some("dev")).is_empty());
+    }
+
+    #[rstest]
+    #[case("not json")]
+    #[case("[]")]
+    #[case(r#"{"dev": "not an object"}"#)]
+    fn test_invalid_files_are_rejected(#[case] json: &str) {
+        let result = Environments::from_json(Some(json), None);
+        assert!(result.is_err());
+        assert!(format!("{:#}", result.unwrap_err()).contains(PUBLIC_ENV_FILE));
+    }
+
+    #[test]
+    fn test_load_reads_files_from_directory() {
+        let dir = std::env::temp_dir().join(format!("http-lsp-env-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+        std::fs::write(dir.join(PUBLIC_ENV_FILE), PUBLIC).expect("Failed to write env file");
+
+        let envs = Environments::load(&dir).expect("Environments should load");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert_eq!(envs.names(), vec!["dev", "staging"]);
+        assert_eq!(
+            envs.variables(Some("dev")).get("token").map(String::as_str),
+            Some("")
+        );
+    }
+
+    #[rstest]
+    #[case(Some("dev"), "token", Some(("dev", PRIVATE_ENV_FILE, "dev-secret")))]
+    #[case(Some("dev"), "baseUrl", Some(("dev", PUBLIC_ENV_FILE, "http://localhost:8080")))]
+    #[case(Some("dev"), "version", Some(("$shared", PUBLIC_ENV_FILE, "v1")))]
+    #[case(None, "version", Some(("$shared", PUBLIC_ENV_FILE, "v1")))]
+    #[case(Some("staging"), "token", None)]
+    fn test_definition(
+        #[case] active: Option<&str>,
+        #[case] name: &str,
+        #[case] expected: Option<(&str, &str, &str)>,
+    ) {
+        let envs = Environments::from_json(Some(PUBLIC), Some(PRIVATE))
+            .expect("Environments should parse");
+
+        let definition = envs.definition(name, active);
+        assert_eq!(
+            definition.map(|d| (d.environment, d.file, d.value)),
+            expected
+        );
+    }
+}
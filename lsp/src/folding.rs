@@ -0,0 +1,69 @@
+use crate::parser::ParsedDocument;
+use std::ops::Range;
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+/// A foldable region for each request block and each body spanning several lines
+pub fn folding_ranges(document: &ParsedDocument) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+
+    for request in &document.requests {
+        ranges.extend(folding_range(
+            &request.block_lines,
+            Some(FoldingRangeKind::Region),
+        ));
+        if let Some(body_lines) = &request.body_lines {
+            ranges.extend(folding_range(body_lines, None));
+        }
+    }
+
+    ranges
+}
+
+fn folding_range(lines: &Range<usize>, kind: Option<FoldingRangeKind>) -> Option<FoldingRange> {
+    (lines.len() > 1).then(|| FoldingRange {
+        start_line: lines.start as u32,
+        end_line: lines.end as u32 - 1,
+        kind,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_document;
+
+    #[test]
+    fn test_folding_ranges_for_blocks_and_bodies() {
+        let content = r#"### Create user
+POST https://example.com/users
+Content-Type: application/json
+
+{
+  "name": "test"
+}
+
+###
+GET https://example.com/health
+###
+POST https://example.com/ping
+
+pong"#;
+
+        let ranges: Vec<(u32, u32, Option<FoldingRangeKind>)> =
+            folding_ranges(&parse_document(content))
+                .into_iter()
+                .map(|range| (range.start_line, range.end_line, range.kind))
+                .collect();
+
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 6, Some(FoldingRangeKind::Region)),
+                (4, 6, None),
+                (8, 9, Some(FoldingRangeKind::Region)),
+                (10, 13, Some(FoldingRangeKind::Region)),
+            ]
+        );
+    }
+}
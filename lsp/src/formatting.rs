@@ -0,0 +1,467 @@
+use crate::completion::HEADER_NAMES;
+use crate::{parser, variables};
+
+/// Part of a request block a line belongs to
+#[derive(Debug, PartialEq, Eq)]
+enum Section {
+    /// Comments and variable declarations before the request line
+    Preamble,
+    Headers,
+    Body,
+}
+
+/// Body formats that are pretty-printed, chosen from the request's Content-Type
+#[derive(Debug, PartialEq, Eq)]
+enum BodyKind {
+    Json,
+    Xml,
+}
+
+/// Normalises a whole document: uppercase methods, canonical `Name: value` headers, one
+/// blank line before each `###` separator, and JSON or XML bodies indented with `indent`.
+/// Comments and variable declarations are kept in place.
+pub fn format_document(text: &str, indent: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    for (idx, line) in lines.iter().enumerate() {
+        if line.trim().starts_with("###") {
+            blocks.push(format_block(&lines[start..idx], indent));
+            start = idx;
+        }
+    }
+    blocks.push(format_block(&lines[start..], indent));
+    blocks.retain(|block| !block.is_empty());
+
+    let mut formatted = blocks
+        .iter()
+        .map(|block| block.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    formatted
+}
+
+/// Formats the lines of one block, starting with its `###` separator if it has one
+fn format_block(lines: &[&str], indent: &str) -> Vec<String> {
+    let mut formatted = Vec::new();
+    let mut lines = lines.iter();
+
+    if let Some(separator) = lines
+        .as_slice()
+        .first()
+        .filter(|l| l.trim().starts_with("###"))
+    {
+        formatted.push(match parser::parse_separator_title(separator) {
+            Some(title) => format!("### {}", title),
+            None => "###".to_string(),
+        });
+        lines.next();
+    }
+
+    let header_len = formatted.len();
+    let mut section = Section::Preamble;
+    let mut pending_blank = false;
+    let mut content_type = None;
+    let mut body = Vec::new();
+
+    for line in lines {
+        let trimmed = line.trim();
+        let is_comment = trimmed.starts_with('#') || trimmed.starts_with("//");
+
+        match section {
+            Section::Preamble => {
+                // Runs of blank lines collapse to one, and none follow the separator
+                if trimmed.is_empty() {
+                    pending_blank = formatted.len() > header_len;
+                    continue;
+                }
+                if std::mem::take(&mut pending_blank) {
+                    formatted.push(String::new());
+                }
+
+                if is_comment {
+                    formatted.push(trimmed.to_string());
+                } else if let Some((name, value)) = variables::parse_variable_declaration(trimmed) {
+                    formatted.push(format!("@{} = {}", name, value));
+                } else if parser::is_request_line(trimmed) {
+                    formatted.push(format_request_line(trimmed));
+                    section = Section::Headers;
+                } else {
+                    formatted.push(trimmed.to_string());
+                }
+            }
+            Section::Headers => {
+                if trimmed.is_empty() {
+                    section = Section::Body;
+                } else if is_comment {
+                    formatted.push(trimmed.to_string());
+                } else if let Some((name, value)) = trimmed.split_once(':') {
+                    let name = canonical_header_name(name.trim());
+                    let value = value.trim();
+                    if name.eq_ignore_ascii_case("content-type") {
+                        content_type = Some(value);
+                    }
+                    formatted.push(format!("{}: {}", name, value));
+                } else {
+                    formatted.push(trimmed.to_string());
+                }
+            }
+            Section::Body => body.push(*line),
+        }
+    }
+
+    let first = body.iter().position(|line| !line.trim().is_empty());
+    let last = body.iter().rposition(|line| !line.trim().is_empty());
+    if let (Some(first), Some(last)) = (first, last) {
+        let body = &body[first..=last];
+        // Comment lines are dropped from the body when it is sent, so never reflow around them
+        let has_comments = body.iter().any(|line| {
+            let trimmed = line.trim();
+            trimmed.starts_with('#') || trimmed.starts_with("//")
+        });
+        let text = body.join("\n");
+        let pretty = match content_type.and_then(body_kind) {
+            _ if has_comments => None,
+            Some(BodyKind::Json) => format_json(text.trim(), indent),
+            Some(BodyKind::Xml) => format_xml(text.trim(), indent),
+            None => None,
+        };
+
+        formatted.push(String::new());
+        formatted.push(pretty.unwrap_or(text));
+    }
+
+    formatted
+}
+
+/// Uppercases the method and separates the request line's parts with single spaces
+fn format_request_line(trimmed: &str) -> String {
+    let mut parts = parser::split_request_line(trimmed);
+    let method = parts.remove(0).to_uppercase();
+    std::iter::once(method.as_str())
+        .chain(parts)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The usual spelling of a header name: the catalog's spelling for standard headers,
+/// otherwise each dash-separated word capitalised. Names with placeholders are kept as written.
+pub fn canonical_header_name(name: &str) -> String {
+    if let Some(known) = HEADER_NAMES
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(name))
+    {
+        return known.to_string();
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return name.to_string();
+    }
+
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn body_kind(content_type: &str) -> Option<BodyKind> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime == "application/json" || mime.ends_with("+json") {
+        Some(BodyKind::Json)
+    } else if mime.ends_with("/xml") || mime.ends_with("+xml") {
+        Some(BodyKind::Xml)
+    } else {
+        None
+    }
+}
+
+/// Re-indents a JSON object or array. Only whitespace outside strings and `{{placeholders}}`
+/// changes, so bodies with unquoted placeholders such as `"id": {{id}}` are formatted too.
+/// Returns `None` when the brackets don't balance.
+fn format_json(body: &str, indent: &str) -> Option<String> {
+    if !body.starts_with(['{', '[']) {
+        return None;
+    }
+
+    let mut formatted = String::new();
+    let mut open = Vec::new();
+    let mut separated = false;
+    let mut chars = body.char_indices().peekable();
+    let newline = |formatted: &mut String, depth: usize| {
+        formatted.push('\n');
+        formatted.push_str(&indent.repeat(depth));
+    };
+
+    while let Some((idx, c)) = chars.next() {
+        let was_separated = std::mem::take(&mut separated);
+        match c {
+            '"' => {
+                let mut escaped = false;
+                let end = chars
+                    .by_ref()
+                    .find(|&(_, c)| {
+                        let closes = c == '"' && !escaped;
+                        escaped = c == '\\' && !escaped;
+                        closes
+                    })
+                    .map(|(end, _)| end)?;
+                formatted.push_str(&body[idx..=end]);
+            }
+            '{' if body[idx..].starts_with("{{") => {
+                let len = body[idx..].find("}}")? + 2;
+                formatted.push_str(&body[idx..idx + len]);
+                while chars.next_if(|&(next, _)| next < idx + len).is_some() {}
+            }
+            '{' | '[' => {
+                let close = if c == '{' { '}' } else { ']' };
+                formatted.push(c);
+                while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                // Empty objects and arrays stay on one line
+                if chars.next_if(|&(_, next)| next == close).is_some() {
+                    formatted.push(close);
+                } else {
+                    open.push(close);
+                    newline(&mut formatted, open.len());
+                }
+            }
+            '}' | ']' => {
+                if open.pop()? != c {
+                    return None;
+                }
+                newline(&mut formatted, open.len());
+                formatted.push(c);
+            }
+            ',' => {
+                formatted.push(c);
+                newline(&mut formatted, open.len());
+            }
+            ':' => formatted.push_str(": "),
+            c if c.is_whitespace() => separated = true,
+            c => {
+                // Keep apart bare tokens that were apart in the original
+                if was_separated && formatted.ends_with(|c: char| !" \n[{,".contains(c)) {
+                    formatted.push(' ');
+                }
+                formatted.push(c);
+            }
+        }
+    }
+
+    open.is_empty().then_some(formatted)
+}
+
+/// Pieces of an XML document, each kept exactly as written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum XmlToken<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    /// Self-closing elements, comments, processing instructions and doctypes
+    Markup(&'a str),
+    /// Trimmed text or a CDATA section
+    Text(&'a str),
+}
+
+/// Puts each element of a well-formed XML document on its own line, keeping elements that
+/// only hold text on one line. Returns `None` for documents that don't parse.
+fn format_xml(body: &str, indent: &str) -> Option<String> {
+    roxmltree::Document::parse(body).ok()?;
+
+    let tokens = xml_tokens(body)?;
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut idx = 0;
+
+    while let Some(token) = tokens.get(idx) {
+        let line = |depth: usize, content: &str| format!("{}{}", indent.repeat(depth), content);
+        idx += 1;
+
+        match *token {
+            XmlToken::Open(open) => match (tokens.get(idx), tokens.get(idx + 1)) {
+                (Some(XmlToken::Text(text)), Some(XmlToken::Close(close))) => {
+                    lines.push(line(depth, &format!("{}{}{}", open, text, close)));
+                    idx += 2;
+                }
+                (Some(XmlToken::Close(close)), _) => {
+                    lines.push(line(depth, &format!("{}{}", open, close)));
+                    idx += 1;
+                }
+                _ => {
+                    lines.push(line(depth, open));
+                    depth += 1;
+                }
+            },
+            XmlToken::Close(close) => {
+                depth = depth.saturating_sub(1);
+                lines.push(line(depth, close));
+            }
+            XmlToken::Markup(content) | XmlToken::Text(content) => lines.push(line(depth, content)),
+        }
+    }
+
+    Some(lines.join("\n"))
+}
+
+fn xml_tokens<'a>(body: &'a str) -> Option<Vec<XmlToken<'a>>> {
+    let mut tokens = Vec::new();
+    let mut rest = body;
+
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = rest[..end].trim();
+            if !text.is_empty() {
+                tokens.push(XmlToken::Text(text));
+            }
+            rest = &rest[end..];
+            continue;
+        }
+
+        let (len, token): (usize, fn(&'a str) -> XmlToken<'a>) = if rest.starts_with("<!--") {
+            (rest.find("-->")? + 3, XmlToken::Markup)
+        } else if rest.starts_with("<![CDATA[") {
+            (rest.find("]]>")? + 3, XmlToken::Text)
+        } else if rest.starts_with("<?") {
+            (rest.find("?>")? + 2, XmlToken::Markup)
+        } else {
+            // Attribute values may contain '>'
+            let mut quote = None;
+            let end = rest.char_indices().find_map(|(idx, c)| match (quote, c) {
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    None
+                }
+                (Some(open), c) if c == open => {
+                    quote = None;
+                    None
+                }
+                (None, '>') => Some(idx),
+                _ => None,
+            })?;
+            let tag = &rest[..=end];
+            let token: fn(&'a str) -> XmlToken<'a> = if tag.starts_with("</") {
+                XmlToken::Close
+            } else if tag.starts_with("<!") || tag.ends_with("/>") {
+                XmlToken::Markup
+            } else {
+                XmlToken::Open
+            };
+            (end + 1, token)
+        };
+
+        tokens.push(token(&rest[..len]));
+        rest = &rest[len..];
+    }
+
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_format_document() {
+        let text = "@host   =  example.com\n\n\n// shared token\n@token=abc\n###   Create user   \n\n\
+                    # @name  createUser\npost   https://{{host}}/users   HTTP/1.1\ncontent-type:application/json\n\
+                    x-request-id :  {{$uuid}}\n\n\n{\"name\": \"Ada\", \"tags\": [], \"id\": {{id}}}\n\n\n\
+                    ###\nget https://{{host}}/users\naccept:   */*   \n";
+
+        assert_eq!(
+            format_document(text, "  "),
+            "@host = example.com\n\n// shared token\n@token = abc\n\n\
+             ### Create user\n# @name  createUser\nPOST https://{{host}}/users HTTP/1.1\n\
+             Content-Type: application/json\nX-Request-Id: {{$uuid}}\n\n\
+             {\n  \"name\": \"Ada\",\n  \"tags\": [],\n  \"id\": {{id}}\n}\n\n\
+             ###\nGET https://{{host}}/users\nAccept: */*\n"
+        );
+    }
+
+    #[test]
+    fn test_format_document_is_idempotent_and_keeps_requests() {
+        let text = "# @name login\npost https://example.com/login\ncontent-type: application/xml\n\n\
+                    <login><user>ada</user><!-- secret --><password>{{password}}</password></login>\n\
+                    ###\n\n\n\ndelete https://example.com/session\n# keep me\nx-trace:1";
+        let formatted = format_document(text, "\t");
+
+        assert_eq!(format_document(&formatted, "\t"), formatted);
+
+        let before = parser::parse_document(text);
+        let after = parser::parse_document(&formatted);
+        let summary = |document: &parser::ParsedDocument| {
+            document
+                .requests
+                .iter()
+                .map(|r| {
+                    (
+                        r.method.clone(),
+                        r.url.clone(),
+                        r.name.clone(),
+                        r.headers.len(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(summary(&before), summary(&after));
+        assert!(formatted.contains("\n# keep me\n"));
+    }
+
+    #[rstest]
+    #[case("content-type", "Content-Type")]
+    #[case("USER-AGENT", "User-Agent")]
+    #[case("te", "TE")]
+    #[case("x-api-key", "X-Api-Key")]
+    #[case("X-{{header}}", "X-{{header}}")]
+    fn test_canonical_header_name(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(canonical_header_name(name), expected);
+    }
+
+    #[rstest]
+    #[case(r#"{"a":{"b":[1,2]},"c":"x, {y}: [z]"}"#, Some("{\n  \"a\": {\n    \"b\": [\n      1,\n      2\n    ]\n  },\n  \"c\": \"x, {y}: [z]\"\n}"))]
+    #[case(
+        r#"[ {{$randomInt 1 10}} , "a\"]" ]"#,
+        Some("[\n  {{$randomInt 1 10}},\n  \"a\\\"]\"\n]")
+    )]
+    #[case(r#"{"a": [1}"#, None)]
+    #[case(r#"{"a": "unterminated}"#, None)]
+    #[case("name=ada", None)]
+    fn test_format_json(#[case] body: &str, #[case] expected: Option<&str>) {
+        assert_eq!(format_json(body, "  ").as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        r#"<?xml version="1.0"?><a x="1>2"><b>text</b><c/><d></d><e><![CDATA[<raw>]]></e></a>"#,
+        Some("<?xml version=\"1.0\"?>\n<a x=\"1>2\">\n  <b>text</b>\n  <c/>\n  <d></d>\n  <e><![CDATA[<raw>]]></e>\n</a>")
+    )]
+    #[case("<a><b></a>", None)]
+    fn test_format_xml(#[case] body: &str, #[case] expected: Option<&str>) {
+        assert_eq!(format_xml(body, "  ").as_deref(), expected);
+    }
+
+    #[test]
+    fn test_body_with_comments_or_other_types_is_kept() {
+        let text =
+            "POST https://example.com\nContent-Type: application/json\n\n{\"a\":1}\n# note\n\
+                    ###\nPOST https://example.com\nContent-Type: text/plain\n\n{\"a\":1}\n";
+
+        assert_eq!(format_document(text, "  "), text.replace("###", "\n###"));
+    }
+}
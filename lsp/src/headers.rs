@@ -0,0 +1,168 @@
+/// Ordered list of HTTP headers that keeps repeated names, with case-insensitive lookups
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header after the existing ones, keeping any earlier values for the same name
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Sets a header, replacing every earlier value for the same name.
+    /// The header keeps the position of its first occurrence.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        match self
+            .entries
+            .iter()
+            .position(|(existing, _)| existing.eq_ignore_ascii_case(&name))
+        {
+            Some(first) => {
+                self.entries[first].1 = value.into();
+                let mut idx = 0;
+                self.entries.retain(|(existing, _)| {
+                    let keep = idx <= first || !existing.eq_ignore_ascii_case(&name);
+                    idx += 1;
+                    keep
+                });
+            }
+            None => self.entries.push((name, value.into())),
+        }
+    }
+
+    /// First value for `name`
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Every value for `name`, in order
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> {
+        self.entries
+            .iter()
+            .filter(move |(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a String, &'a String);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, String)>,
+        fn(&'a (String, String)) -> (&'a String, &'a String),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+}
+
+impl<N: Into<String>, V: Into<String>> FromIterator<(N, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        let mut headers = Headers::new();
+        for (name, value) in iter {
+            headers.append(name, value);
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn create_test_headers() -> Headers {
+        Headers::from_iter([
+            ("Accept", "application/json"),
+            ("Set-Cookie", "a=1"),
+            ("X-Trace", "abc"),
+            ("set-cookie", "b=2"),
+        ])
+    }
+
+    #[test]
+    fn test_append_keeps_duplicates_in_order() {
+        let headers = create_test_headers();
+        let entries: Vec<(&String, &String)> = headers.iter().collect();
+
+        assert_eq!(headers.len(), 4);
+        assert_eq!(entries[1], (&"Set-Cookie".to_string(), &"a=1".to_string()));
+        assert_eq!(entries[3], (&"set-cookie".to_string(), &"b=2".to_string()));
+    }
+
+    #[rstest]
+    #[case("Set-Cookie", vec!["a=1", "b=2"])]
+    #[case("SET-COOKIE", vec!["a=1", "b=2"])]
+    #[case("accept", vec!["application/json"])]
+    #[case("Missing", vec![])]
+    fn test_get_all_is_case_insensitive(#[case] name: &str, #[case] expected: Vec<&str>) {
+        let headers = create_test_headers();
+        let values: Vec<&str> = headers.get_all(name).map(String::as_str).collect();
+
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_get_returns_first_value() {
+        let headers = create_test_headers();
+
+        assert_eq!(headers.get("set-cookie"), Some(&"a=1".to_string()));
+        assert!(headers.contains_key("x-trace"));
+        assert!(!headers.contains_key("missing"));
+    }
+
+    #[test]
+    fn test_insert_replaces_all_values_at_first_position() {
+        let mut headers = create_test_headers();
+        headers.insert("SET-COOKIE", "c=3");
+
+        let entries: Vec<(&str, &str)> = headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("Accept", "application/json"),
+                ("Set-Cookie", "c=3"),
+                ("X-Trace", "abc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_adds_new_header() {
+        let mut headers = Headers::new();
+        assert!(headers.is_empty());
+
+        headers.insert("Accept", "text/plain");
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("accept"), Some(&"text/plain".to_string()));
+    }
+}
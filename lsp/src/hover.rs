@@ -0,0 +1,362 @@
+use crate::environment::Environments;
+use crate::executor::HttpResponse;
+use crate::parser::{self, ParsedDocument};
+use crate::system_variables::{self, SYSTEM_VARIABLES};
+use crate::{document, response_variables, variables};
+use std::collections::HashMap;
+use std::path::Path;
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position, Range};
+
+/// Everything a `{{placeholder}}` can be resolved against, tried in the same order as when
+/// a request is sent
+pub struct Scope<'a> {
+    pub document: &'a ParsedDocument,
+    pub environments: &'a Environments,
+    pub active_environment: Option<&'a str>,
+    pub named_responses: &'a HashMap<String, HttpResponse>,
+    pub document_dir: Option<&'a Path>,
+}
+
+/// A placeholder's value, if it can be known before sending, and where it comes from
+struct Lookup {
+    value: Option<String>,
+    source: String,
+}
+
+impl Scope<'_> {
+    /// Looks up an expression used on `line`, seeing only file variables declared above it
+    fn lookup(&self, expression: &str, line: usize) -> Lookup {
+        if let Some(declaration) = self.document.declaration(expression, line) {
+            return Lookup {
+                value: Some(declaration.value.clone()),
+                source: format!("File variable declared on line {}", declaration.line + 1),
+            };
+        }
+
+        if let Some(name) = expression
+            .split_whitespace()
+            .next()
+            .filter(|name| name.starts_with('$'))
+        {
+            return self.lookup_system_variable(name, expression);
+        }
+
+        if let Some(reference) = response_variables::parse_reference(expression) {
+            let name = reference.request_name;
+            let Some(request) = self
+                .document
+                .requests
+                .iter()
+                .find(|request| request.name.as_deref() == Some(name))
+            else {
+                return Lookup {
+                    value: None,
+                    source: format!("Request '{}' is not defined in this document", name),
+                };
+            };
+
+            let defined = format!(
+                "Response of request '{}' on line {}",
+                name,
+                request.line_number + 1
+            );
+            return match self.named_responses.get(name) {
+                Some(response) => match response_variables::resolve(&reference, response) {
+                    Ok(value) => Lookup {
+                        value: Some(value),
+                        source: defined,
+                    },
+                    Err(e) => Lookup {
+                        value: None,
+                        source: format!("{}: {}", defined, e),
+                    },
+                },
+                None => Lookup {
+                    value: None,
+                    source: format!("{}, which has not been sent yet", defined),
+                },
+            };
+        }
+
+        if let Some(definition) = self
+            .environments
+            .definition(expression, self.active_environment)
+        {
+            return Lookup {
+                value: Some(definition.value.to_string()),
+                source: format!(
+                    "Environment variable from `{}` in `{}`",
+                    definition.environment, definition.file
+                ),
+            };
+        }
+
+        Lookup {
+            value: None,
+            source: match self.active_environment {
+                Some(active) => format!(
+                    "Undefined: not declared in this file or the '{}' environment",
+                    active
+                ),
+                None => "Undefined: not declared in this file and no environment is selected"
+                    .to_string(),
+            },
+        }
+    }
+
+    fn lookup_system_variable(&self, name: &str, expression: &str) -> Lookup {
+        let Some((_, description)) = SYSTEM_VARIABLES
+            .iter()
+            .find(|(usage, _)| usage.split(' ').next() == Some(name))
+        else {
+            return Lookup {
+                value: None,
+                source: format!("Unknown system variable: {}", name),
+            };
+        };
+
+        // Only environment lookups give the same value every time
+        if name != "$processEnv" && name != "$dotenv" {
+            return Lookup {
+                value: None,
+                source: format!("{}, generated each time the request is sent", description),
+            };
+        }
+
+        match system_variables::evaluate(expression, self.document_dir) {
+            Some(Ok(value)) => Lookup {
+                value: Some(value),
+                source: description.to_string(),
+            },
+            Some(Err(e)) => Lookup {
+                value: None,
+                source: e,
+            },
+            None => Lookup {
+                value: None,
+                source: description.to_string(),
+            },
+        }
+    }
+}
+
+/// Hover card for the placeholder under the cursor, or the expanded URL on a request line
+pub fn hover(text: &str, position: Position, scope: &Scope) -> Option<Hover> {
+    let line = text.lines().nth(position.line as usize)?;
+    let cursor = document::offset_at(line, Position::new(0, position.character));
+
+    if let Some(placeholder) = variables::placeholder_spans(line)
+        .into_iter()
+        .find(|placeholder| placeholder.start <= cursor && cursor <= placeholder.end)
+    {
+        let lookup = scope.lookup(placeholder.expression, position.line as usize);
+        let heading = match &lookup.value {
+            Some(value) => format!("`{}` = `{}`", placeholder.expression, value),
+            None => format!("`{}`", placeholder.expression),
+        };
+
+        let column = |offset: usize| parser::utf16_len(&line[..offset]) as u32;
+        return Some(markdown_hover(
+            format!("{}\n\n{}", heading, lookup.source),
+            Some(Range::new(
+                Position::new(position.line, column(placeholder.start)),
+                Position::new(position.line, column(placeholder.end)),
+            )),
+        ));
+    }
+
+    let request = scope
+        .document
+        .requests
+        .iter()
+        .find(|request| request.line_number == position.line as usize)?;
+
+    let url = variables::substitute_with(&request.url, |expression| {
+        scope.lookup(expression, request.line_number).value
+    });
+    let mut contents = format!("**{}** `{}`", request.method, url);
+    let remaining = variables::placeholders(&url);
+    if !remaining.is_empty() {
+        let remaining: Vec<String> = remaining
+            .iter()
+            .map(|expression| format!("`{{{{{}}}}}`", expression))
+            .collect();
+        contents.push_str(&format!(
+            "\n\nNot expanded until sent: {}",
+            remaining.join(", ")
+        ));
+    }
+
+    Some(markdown_hover(contents, None))
+}
+
+fn markdown_hover(value: String, range: Option<Range>) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "@path = /users
+# @name login
+POST {{baseUrl}}/login
+
+###
+GET {{baseUrl}}{{path}}/{{login.response.body.$.id}}?t={{$timestamp}}
+Authorization: Bearer {{login.response.body.$.token}}
+X-Missing: {{missing}}
+X-Profile: {{profile.response.body}}";
+
+    fn hover_text(
+        position: Position,
+        active_environment: Option<&str>,
+        named_responses: &HashMap<String, HttpResponse>,
+    ) -> Option<String> {
+        let document = parser::parse_document(DOCUMENT);
+        let environments = Environments::from_json(
+            Some(r#"{"dev": {"baseUrl": "https://dev.example.com"}}"#),
+            None,
+        )
+        .expect("Environments should parse");
+        let scope = Scope {
+            document: &document,
+            environments: &environments,
+            active_environment,
+            named_responses,
+            document_dir: None,
+        };
+
+        hover(DOCUMENT, position, &scope).map(|hover| match hover.contents {
+            HoverContents::Markup(markup) => markup.value,
+            other => panic!("unexpected hover contents: {:?}", other),
+        })
+    }
+
+    fn login_response() -> HashMap<String, HttpResponse> {
+        HashMap::from([(
+            "login".to_string(),
+            HttpResponse {
+                status: 200,
+                status_text: "OK".to_string(),
+                headers: Headers::new(),
+                body: r#"{"id": 7, "token": "abc"}"#.to_string(),
+                raw_body: r#"{"id": 7, "token": "abc"}"#.into(),
+                duration_ms: 5,
+                timings: Timings::default(),
+            },
+        )])
+    }
+
+    #[rstest]
+    #[case(2, 8, Some("dev"), "`baseUrl` = `https://dev.example.com`")]
+    #[case(2, 8, Some("dev"), "from `dev` in `http-client.env.json`")]
+    #[case(2, 8, None, "Undefined: not declared in this file and no environment")]
+    #[case(5, 18, None, "`path` = `/users`\n\nFile variable declared on line 1")]
+    #[case(5, 60, None, "generated each time the request is sent")]
+    #[case(
+        5,
+        30,
+        None,
+        "Response of request 'login' on line 3, which has not been sent"
+    )]
+    #[case(
+        7,
+        14,
+        Some("dev"),
+        "Undefined: not declared in this file or the 'dev' environment"
+    )]
+    #[case(8, 14, None, "Request 'profile' is not defined in this document")]
+    fn test_hover_on_placeholder(
+        #[case] line: u32,
+        #[case] character: u32,
+        #[case] active: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let text = hover_text(Position::new(line, character), active, &HashMap::new())
+            .expect("Expected a hover");
+
+        assert!(text.contains(expected), "unexpected hover: {}", text);
+    }
+
+    #[test]
+    fn test_hover_on_response_reference_shows_value() {
+        let text =
+            hover_text(Position::new(6, 30), None, &login_response()).expect("Expected a hover");
+
+        assert!(text.starts_with("`login.response.body.$.token` = `abc`"));
+    }
+
+    #[test]
+    fn test_hover_on_request_line_expands_url() {
+        let text = hover_text(Position::new(5, 1), Some("dev"), &login_response())
+            .expect("Expected a hover");
+
+        assert_eq!(
+            text,
+            "**GET** `https://dev.example.com/users/7?t={{$timestamp}}`\n\n\
+             Not expanded until sent: `{{$timestamp}}`"
+        );
+    }
+
+    #[test]
+    fn test_hover_range_covers_placeholder() {
+        let document = parser::parse_document(DOCUMENT);
+        let environments = Environments::default();
+        let named_responses = HashMap::new();
+        let scope = Scope {
+            document: &document,
+            environments: &environments,
+            active_environment: None,
+            named_responses: &named_responses,
+            document_dir: None,
+        };
+
+        let hover = hover(DOCUMENT, Position::new(2, 8), &scope).expect("Expected a hover");
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(2, 5), Position::new(2, 16)))
+        );
+    }
+
+    #[rstest]
+    #[case(1, "`host` = `a.com`\n\nFile variable declared on line 1")]
+    #[case(4, "`host` = `b.com`\n\nFile variable declared on line 4")]
+    fn test_hover_on_redeclared_variable(#[case] line: u32, #[case] expected: &str) {
+        let text =
+            "@host = a.com\nGET https://{{host}}/\n###\n@host = b.com\nGET https://{{host}}/";
+        let document = parser::parse_document(text);
+        let environments = Environments::default();
+        let named_responses = HashMap::new();
+        let scope = Scope {
+            document: &document,
+            environments: &environments,
+            active_environment: None,
+            named_responses: &named_responses,
+            document_dir: None,
+        };
+
+        let hover = hover(text, Position::new(line, 15), &scope).expect("Expected a hover");
+        assert_eq!(
+            hover.contents,
+            HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: expected.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_no_hover_outside_placeholders_and_request_lines() {
+        assert!(hover_text(Position::new(1, 3), None, &HashMap::new()).is_none());
+    }
+}
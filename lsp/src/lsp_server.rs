@@ -1,16 +1,45 @@
-use crate::{executor, parser};
-use std::collections::HashMap;
+use crate::cookies::{self, CookieJar, SharedCookieJar};
+use crate::document::{self, Document};
+use crate::environment::{self, Environments};
+use crate::settings::Settings;
+use crate::{
+    completion, curl, dependencies, executor, folding, formatting, hover, navigation, parser,
+    response_body, response_variables, run, semantic_tokens, snippets, symbols, system_variables,
+    variables,
+};
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
+/// Environments read from a directory, or why its environment files could not be read
+type LoadedEnvironments = std::result::Result<Arc<Environments>, String>;
+
 pub struct HttpLspServer {
     client: Client,
-    document_map: Arc<Mutex<HashMap<Url, String>>>,
+    document_map: Arc<Mutex<HashMap<Url, Document>>>,
+    workspace_root: Arc<Mutex<Option<PathBuf>>>,
+    active_environment: Arc<Mutex<Option<String>>>,
+    /// Environment files of each directory, cached until a file watcher reports a change
+    environments: Arc<Mutex<HashMap<PathBuf, LoadedEnvironments>>>,
+    /// Whether the client watches environment files for us, which makes caching them safe
+    watching_environment_files: Arc<Mutex<bool>>,
+    /// Last response of each `# @name`d request in each document, for `{{name.response...}}`
+    /// references made from the same document
+    named_responses: Arc<Mutex<HashMap<Url, HashMap<String, executor::HttpResponse>>>>,
+    settings: Arc<Mutex<Settings>>,
+    /// Long-lived clients, one per profile, whose connection pools are shared by every request
+    http_clients: Arc<Mutex<HashMap<executor::ClientProfile, reqwest::Client>>>,
+    /// Cookies set by responses, sent with later requests unless they opt out
+    cookie_jar: Arc<SharedCookieJar>,
+    /// Requests in the workspace's .http files, for workspace symbols
+    workspace_index: Arc<std::sync::Mutex<symbols::WorkspaceIndex>>,
 }
 
 impl HttpLspServer {
@@ -21,6 +50,15 @@ impl HttpLspServer {
         HttpLspServer {
             client,
             document_map: Arc::new(Mutex::new(HashMap::new())),
+            workspace_root: Arc::new(Mutex::new(None)),
+            active_environment: Arc::new(Mutex::new(None)),
+            environments: Arc::new(Mutex::new(HashMap::new())),
+            watching_environment_files: Arc::new(Mutex::new(false)),
+            named_responses: Arc::new(Mutex::new(HashMap::new())),
+            settings: Arc::new(Mutex::new(Settings::default())),
+            http_clients: Arc::new(Mutex::new(HashMap::new())),
+            cookie_jar: Arc::new(SharedCookieJar::default()),
+            workspace_index: Arc::new(std::sync::Mutex::new(symbols::WorkspaceIndex::default())),
         }
     }
 
@@ -53,6 +91,27 @@ impl LanguageServer for HttpLspServer {
             )
             .await;
 
+        #[allow(deprecated)]
+        let root_uri = params
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.first())
+            .map(|folder| folder.uri.clone())
+            .or(params.root_uri);
+        *self.workspace_root.lock().await = root_uri.and_then(|uri| uri.to_file_path().ok());
+
+        if let Some(options) = &params.initialization_options {
+            self.apply_settings(options).await;
+        }
+
+        *self.watching_environment_files.lock().await = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
                 name: "HTTP LSP".to_string(),
@@ -60,14 +119,44 @@ impl LanguageServer for HttpLspServer {
             }),
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 code_lens_provider: Some(CodeLensOptions {
                     resolve_provider: Some(false),
                 }),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::legend(),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..Default::default()
+                        },
+                    ),
+                ),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["{".to_string(), ":".to_string()]),
+                    ..Default::default()
+                }),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec!["http.sendRequest".to_string()],
+                    commands: vec![
+                        "http.sendRequest".to_string(),
+                        "http.sendAll".to_string(),
+                        "http.sendRange".to_string(),
+                        "http.switchEnvironment".to_string(),
+                        "http.copyAsCurl".to_string(),
+                        "http.generateCode".to_string(),
+                        "http.viewCookies".to_string(),
+                        "http.clearCookies".to_string(),
+                    ],
                     ..Default::default()
                 }),
                 ..Default::default()
@@ -79,6 +168,10 @@ impl LanguageServer for HttpLspServer {
         self.client
             .log_message(MessageType::INFO, "HTTP LSP server initialized")
             .await;
+
+        if *self.watching_environment_files.lock().await {
+            self.watch_environment_files().await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -87,31 +180,63 @@ impl LanguageServer for HttpLspServer {
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         let uri = params.text_document.uri;
-        let text = params.text_document.text;
+        let mut document = Document::new(params.text_document.text, params.text_document.version);
+        let diagnostics = Self::diagnostics(&document.parsed());
 
-        self.document_map.lock().await.insert(uri.clone(), text);
+        self.document_map.lock().await.insert(uri.clone(), document);
 
         self.client
             .log_message(MessageType::INFO, format!("Opened document: {}", uri))
             .await;
+
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(params.text_document.version))
+            .await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri;
+        let version = params.text_document.version;
 
-        if let Some(change) = params.content_changes.first() {
-            self.document_map
-                .lock()
-                .await
-                .insert(uri.clone(), change.text.clone());
+        let mut document_map = self.document_map.lock().await;
+        let Some(document) = document_map.get_mut(&uri) else {
+            return;
+        };
+        if !document.apply_changes(&params.content_changes, version) {
+            return;
         }
+        let diagnostics = Self::diagnostics(&document.parsed());
+        drop(document_map);
+
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.document_map
-            .lock()
-            .await
-            .remove(&params.text_document.uri);
+        let uri = params.text_document.uri;
+        self.document_map.lock().await.remove(&uri);
+
+        // Clear the closed document's diagnostics
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        self.apply_settings(&params.settings).await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut environments = self.environments.lock().await;
+        for change in params.changes {
+            let dir = change
+                .uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| path.parent().map(|p| p.to_path_buf()));
+            if let Some(dir) = dir {
+                environments.remove(&dir);
+            }
+        }
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
@@ -123,20 +248,14 @@ impl LanguageServer for HttpLspServer {
             uri, line
         ));
 
-        let document_map = self.document_map.lock().await;
-        let content = match document_map.get(&uri) {
-            Some(content) => content.clone(),
-            None => {
-                Self::log_to_file("Document not found in map");
-                return Ok(None);
-            }
+        let Some(document) = self.parsed_document(&uri).await else {
+            Self::log_to_file("Document not found in map");
+            return Ok(None);
         };
-        drop(document_map);
-
-        let requests = parser::parse_http_file(&content);
+        let requests = &document.requests;
 
         Self::log_to_file(&format!("Found {} HTTP requests", requests.len()));
-        for req in &requests {
+        for req in requests {
             Self::log_to_file(&format!(
                 "  - {} {} at line {}",
                 req.method, req.url, req.line_number
@@ -149,6 +268,8 @@ impl LanguageServer for HttpLspServer {
             .filter(|r| r.line_number <= line)
             .max_by_key(|r| r.line_number);
 
+        let mut actions = Vec::new();
+
         if let Some(request) = request {
             Self::log_to_file(&format!(
                 "Found request {} at line {}",
@@ -175,245 +296,1564 @@ impl LanguageServer for HttpLspServer {
                 data: None,
             });
 
-            return Ok(Some(vec![action]));
+            actions.push(action);
+
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Convert to cURL".to_string(),
+                kind: Some(CodeActionKind::EMPTY),
+                command: Some(Command {
+                    title: "Convert to cURL".to_string(),
+                    command: "http.copyAsCurl".to_string(),
+                    arguments: Some(vec![
+                        serde_json::to_value(uri.to_string())
+                            .expect("Failed to serialize URI string"),
+                        serde_json::to_value(request.line_number)
+                            .expect("Failed to serialize line number"),
+                    ]),
+                }),
+                ..Default::default()
+            }));
+
+            for language in snippets::Language::ALL {
+                let title = format!("Generate {} code", language.label());
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.clone(),
+                    kind: Some(CodeActionKind::EMPTY),
+                    command: Some(Command {
+                        title,
+                        command: "http.generateCode".to_string(),
+                        arguments: Some(vec![
+                            serde_json::to_value(uri.to_string())
+                                .expect("Failed to serialize URI string"),
+                            serde_json::to_value(request.line_number)
+                                .expect("Failed to serialize line number"),
+                            serde_json::to_value(language.id())
+                                .expect("Failed to serialize language"),
+                        ]),
+                    }),
+                    ..Default::default()
+                }));
+            }
+        }
+
+        // Offer sending the selected requests, or the whole file, in one run
+        let selection = params.range.start.line as usize..=params.range.end.line as usize;
+        let selected = run::requests_in_range(requests, *selection.start(), *selection.end());
+        if selected.len() > 1 {
+            let title = format!("▶ Send {} Requests in Selection", selected.len());
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.clone(),
+                kind: Some(CodeActionKind::EMPTY),
+                command: Some(Command {
+                    title,
+                    command: "http.sendRange".to_string(),
+                    arguments: Some(vec![
+                        serde_json::to_value(uri.to_string())
+                            .expect("Failed to serialize URI string"),
+                        serde_json::to_value(selection.start())
+                            .expect("Failed to serialize line number"),
+                        serde_json::to_value(selection.end())
+                            .expect("Failed to serialize line number"),
+                    ]),
+                }),
+                ..Default::default()
+            }));
+        }
+        if requests.len() > 1 {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("▶ Send All {} Requests", requests.len()),
+                kind: Some(CodeActionKind::EMPTY),
+                command: Some(Self::send_all_command(&uri, requests.len())),
+                ..Default::default()
+            }));
+        }
+
+        let cookie_count = self.cookie_jar.lock().cookies().len();
+        if cookie_count > 0 {
+            for (title, command) in [
+                (format!("View {} Cookies", cookie_count), "http.viewCookies"),
+                (
+                    format!("Clear {} Cookies", cookie_count),
+                    "http.clearCookies",
+                ),
+            ] {
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.clone(),
+                    kind: Some(CodeActionKind::EMPTY),
+                    command: Some(Command {
+                        title,
+                        command: command.to_string(),
+                        arguments: None,
+                    }),
+                    ..Default::default()
+                }));
+            }
+        }
+
+        // Offer turning a pasted cURL command into a request block
+        let text = self.document_text(&uri).await.unwrap_or_default();
+        if let Some((lines, command)) = curl::command_at(&text, line) {
+            let title = "Convert cURL command to request".to_string();
+            actions.push(CodeActionOrCommand::CodeAction(
+                match curl::from_curl(&command) {
+                    Ok(block) => {
+                        let last_line = lines.end - 1;
+                        let end = text.lines().nth(last_line).map_or(0, parser::utf16_len);
+                        let edit = TextEdit::new(
+                            Range::new(
+                                Position::new(lines.start as u32, 0),
+                                Position::new(last_line as u32, end as u32),
+                            ),
+                            block,
+                        );
+                        CodeAction {
+                            title,
+                            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                            edit: Some(WorkspaceEdit {
+                                changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                                ..Default::default()
+                            }),
+                            is_preferred: Some(true),
+                            ..Default::default()
+                        }
+                    }
+                    Err(reason) => CodeAction {
+                        title,
+                        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                        disabled: Some(CodeActionDisabled { reason }),
+                        ..Default::default()
+                    },
+                },
+            ));
+        }
+
+        // Offer switching to every environment other than the active one
+        let environments = self.load_environments(&uri).await;
+        let active = self.active_environment.lock().await.clone();
+        for name in environments.names() {
+            if active.as_deref() == Some(name) {
+                continue;
+            }
+
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Switch to '{}' environment", name),
+                kind: Some(CodeActionKind::EMPTY),
+                command: Some(Command {
+                    title: format!("Switch to '{}' environment", name),
+                    command: "http.switchEnvironment".to_string(),
+                    arguments: Some(vec![
+                        serde_json::to_value(uri.to_string())
+                            .expect("Failed to serialize URI string"),
+                        serde_json::to_value(name).expect("Failed to serialize environment"),
+                    ]),
+                }),
+                ..Default::default()
+            }));
+        }
+
+        if actions.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(actions))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let environment_variables = self
+            .load_environments(&uri)
+            .await
+            .variables(self.active_environment.lock().await.as_deref());
+
+        let mut document_map = self.document_map.lock().await;
+        let Some(document) = document_map.get_mut(&uri) else {
+            return Ok(None);
+        };
+        let file_variables = document.parsed().variables_at(position.line as usize);
+
+        let items = completion::completions(
+            document.text(),
+            position,
+            &file_variables,
+            &environment_variables,
+        );
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let environments = self.load_environments(&uri).await;
+        let active_environment = self.active_environment.lock().await.clone();
+        let document_dir = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.parent().map(|p| p.to_path_buf()));
+
+        let named_responses = self.named_responses.lock().await;
+        let no_responses = HashMap::new();
+        let mut document_map = self.document_map.lock().await;
+        let Some(document) = document_map.get_mut(&uri) else {
+            return Ok(None);
+        };
+        let parsed = document.parsed();
+
+        let scope = hover::Scope {
+            document: &parsed,
+            environments: &environments,
+            active_environment: active_environment.as_deref(),
+            named_responses: named_responses.get(&uri).unwrap_or(&no_responses),
+            document_dir: document_dir.as_deref(),
+        };
+        Ok(hover::hover(document.text(), position, &scope))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some(text) = self.document_text(&uri).await else {
+            return Ok(None);
+        };
+        let Some(symbol) = navigation::symbol_at(&text, position) else {
+            return Ok(None);
+        };
+
+        if let Some(range) = navigation::definition(&text, &symbol, position.line as usize) {
+            return Ok(Some(GotoDefinitionResponse::Scalar(Location::new(
+                uri, range,
+            ))));
+        }
+
+        // Variables not declared in the file come from the active environment
+        let navigation::Symbol::Variable(name) = symbol else {
+            return Ok(None);
+        };
+        let environments = self.load_environments(&uri).await;
+        let active = self.active_environment.lock().await.clone();
+        let Some(definition) = environments.definition(&name, active.as_deref()) else {
+            return Ok(None);
+        };
+
+        let location = self
+            .environment_key_locations(&uri, &name)
+            .await
+            .into_iter()
+            .find(|(file, environment, _)| {
+                *file == definition.file && *environment == definition.environment
+            })
+            .map(|(_, _, location)| GotoDefinitionResponse::Scalar(location));
+        Ok(location)
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let include_declaration = params.context.include_declaration;
+
+        let Some(text) = self.document_text(&uri).await else {
+            return Ok(None);
+        };
+        let Some(symbol) = navigation::symbol_at(&text, position) else {
+            return Ok(None);
+        };
+
+        let mut locations: Vec<Location> =
+            navigation::references(&text, &symbol, include_declaration)
+                .into_iter()
+                .map(|range| Location::new(uri.clone(), range))
+                .collect();
+
+        if let (navigation::Symbol::Variable(name), true) = (&symbol, include_declaration) {
+            locations.extend(
+                self.environment_key_locations(&uri, name)
+                    .await
+                    .into_iter()
+                    .map(|(_, _, location)| location),
+            );
+        }
+
+        Ok(Some(locations))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let new_name = params.new_name;
+
+        if !variables::is_variable_name(&new_name) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "'{}' is not a valid name: use letters, digits, '_' and '-'",
+                new_name
+            )));
+        }
+
+        let Some(text) = self.document_text(&uri).await else {
+            return Ok(None);
+        };
+        let Some(symbol) = navigation::symbol_at(&text, position) else {
+            return Ok(None);
+        };
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        changes.insert(
+            uri.clone(),
+            navigation::references(&text, &symbol, true)
+                .into_iter()
+                .map(|range| TextEdit::new(range, new_name.clone()))
+                .collect(),
+        );
+
+        if let navigation::Symbol::Variable(name) = &symbol {
+            for (_, _, location) in self.environment_key_locations(&uri, name).await {
+                changes
+                    .entry(location.uri)
+                    .or_default()
+                    .push(TextEdit::new(location.range, new_name.clone()));
+            }
+        }
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let Some(document) = self.parsed_document(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+
+        Ok(Some(DocumentSymbolResponse::Nested(
+            symbols::document_symbols(&document),
+        )))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let Some(document) = self.parsed_document(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+
+        Ok(Some(folding::folding_ranges(&document)))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let Some(text) = self.document_text(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+
+        let indent = if params.options.insert_spaces {
+            " ".repeat(params.options.tab_size as usize)
+        } else {
+            "\t".to_string()
+        };
+        let formatted = formatting::format_document(&text, &indent);
+        if formatted == text {
+            return Ok(Some(Vec::new()));
+        }
+
+        Ok(Some(vec![TextEdit::new(
+            Range::new(
+                Position::new(0, 0),
+                document::position_at(&text, text.len()),
+            ),
+            formatted,
+        )]))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let uri = params.text_document.uri;
+        let environment_variables = self
+            .load_environments(&uri)
+            .await
+            .variables(self.active_environment.lock().await.as_deref());
+
+        let mut document_map = self.document_map.lock().await;
+        let Some(document) = document_map.get_mut(&uri) else {
+            return Ok(None);
+        };
+        let parsed = document.parsed();
+
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens::semantic_tokens(
+                document.text(),
+                &parsed,
+                &environment_variables,
+            ),
+        })))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let mut results = Vec::new();
+
+        // Open documents may have unsaved changes, so search them instead of their files
+        let mut document_map = self.document_map.lock().await;
+        for (uri, document) in document_map.iter_mut() {
+            results.extend(symbols::named_request_symbols(
+                uri,
+                &document.parsed().requests,
+                &params.query,
+            ));
+        }
+        let open_uris: Vec<Url> = document_map.keys().cloned().collect();
+        drop(document_map);
+
+        if let Some(root) = self.workspace_root.lock().await.clone() {
+            // Walking and reading the workspace blocks, so keep it off the async runtime
+            let index = Arc::clone(&self.workspace_index);
+            let query = params.query.clone();
+            let found = tokio::task::spawn_blocking(move || {
+                index
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .named_request_symbols(&root, &open_uris, &query)
+            })
+            .await;
+            results.extend(found.unwrap_or_default());
+        }
+
+        Ok(Some(results))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+
+        Self::log_to_file(&format!("Code lens requested for: {}", uri));
+
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!("Code lens requested for: {}", uri),
+            )
+            .await;
+
+        let Some(document) = self.parsed_document(&uri).await else {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Document not found in map: {}", uri),
+                )
+                .await;
+            return Ok(None);
+        };
+        let requests = &document.requests;
+
+        Self::log_to_file(&format!("Found {} HTTP requests", requests.len()));
+        for req in requests {
+            Self::log_to_file(&format!(
+                "  - {} {} at line {}",
+                req.method, req.url, req.line_number
+            ));
+        }
+
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!("Found {} HTTP requests", requests.len()),
+            )
+            .await;
+
+        let mut lenses = Vec::new();
+
+        if requests.len() > 1 {
+            lenses.push(CodeLens {
+                range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                command: Some(Self::send_all_command(&uri, requests.len())),
+                data: None,
+            });
+        }
+
+        for request in requests {
+            Self::log_to_file(&format!(
+                "Creating lens for {} at line {}",
+                request.method, request.line_number
+            ));
+
+            // Create a code lens above the request line
+            let range = Range {
+                start: Position {
+                    line: request.line_number as u32,
+                    character: 0,
+                },
+                end: Position {
+                    line: request.line_number as u32,
+                    character: 0,
+                },
+            };
+
+            let lens = CodeLens {
+                range,
+                command: Some(Command {
+                    title: format!("▶ Send {} Request", request.method),
+                    command: "http.sendRequest".to_string(),
+                    arguments: Some(vec![
+                        serde_json::to_value(uri.to_string())
+                            .expect("Failed to serialize URI string"),
+                        serde_json::to_value(request.line_number)
+                            .expect("Failed to serialize line number"),
+                    ]),
+                }),
+                data: None,
+            };
+
+            lenses.push(lens);
+        }
+
+        Self::log_to_file(&format!("Returning {} code lenses", lenses.len()));
+
+        Ok(Some(lenses))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        match params.command.as_str() {
+            "http.sendRequest" => self.send_request(params.arguments).await,
+            "http.sendAll" | "http.sendRange" => {
+                self.send_many(
+                    params.arguments,
+                    params.work_done_progress_params.work_done_token,
+                )
+                .await
+            }
+            "http.switchEnvironment" => self.switch_environment(params.arguments).await,
+            "http.copyAsCurl" => self.copy_as_curl(params.arguments).await,
+            "http.generateCode" => self.generate_code(params.arguments).await,
+            "http.viewCookies" => self.view_cookies().await,
+            "http.clearCookies" => self.clear_cookies().await,
+            _ => Ok(None),
+        }
+    }
+}
+
+impl HttpLspServer {
+    async fn send_request(
+        &self,
+        args: Vec<serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>> {
+        if args.len() < 2 {
+            return Ok(None);
+        }
+
+        let uri_str = args[0].as_str().unwrap_or("");
+        let line_number: usize = args[1].as_u64().unwrap_or(0) as usize;
+
+        let uri = match Url::parse(uri_str) {
+            Ok(u) => u,
+            Err(e) => {
+                self.client
+                    .log_message(MessageType::ERROR, format!("Invalid URI: {}", e))
+                    .await;
+                return Ok(None);
+            }
+        };
+
+        let Some(document) = self.parsed_document(&uri).await else {
+            self.client
+                .log_message(MessageType::ERROR, "Document not found")
+                .await;
+            return Ok(None);
+        };
+
+        // Find the request at the specified line
+        let requests = &document.requests;
+        let Some(request) = requests.iter().find(|r| r.line_number == line_number) else {
+            return Ok(None);
+        };
+
+        let mut sent = Vec::new();
+        let result = self
+            .send_with_prerequisites(&uri, request, requests, &mut sent)
+            .await;
+        let last = if result.is_ok() { sent.pop() } else { None };
+
+        // Prerequisites that were sent keep their output even if the request itself failed
+        for (prerequisite, response) in &sent {
+            if let Err(e) = self.append_response_output(&uri, prerequisite, response) {
+                Self::log_to_file(&format!("Failed to write response: {}", e));
+            }
+        }
+
+        let (request, response) = match (result, last) {
+            (Ok(()), Some(exchange)) => exchange,
+            (Err(e), _) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("{:#}", e))
+                    .await;
+                return Ok(None);
+            }
+            (Ok(()), None) => return Ok(None),
+        };
+
+        match self.append_response_output(&uri, &request, &response) {
+            Ok(()) if request.has_directive(parser::NO_LOG) => {
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!("✓ {} - Response not logged (@no-log)", response.summary()),
+                    )
+                    .await;
+            }
+            Ok(()) => {
+                // Show success message with file location
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!(
+                            "✓ {} - Response appended to http-responses.http",
+                            response.summary()
+                        ),
+                    )
+                    .await;
+            }
+            Err(e) => {
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Failed to write response: {}", e),
+                    )
+                    .await;
+            }
+        }
+
+        Ok(Some(
+            serde_json::to_value(response.summary()).expect("Failed to serialize response summary"),
+        ))
+    }
+
+    /// Sends every request of a document, or those overlapping the given lines, one after
+    /// another, then appends their responses and a combined summary to the output file
+    async fn send_many(
+        &self,
+        args: Vec<serde_json::Value>,
+        token: Option<ProgressToken>,
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = args
+            .first()
+            .and_then(|arg| arg.as_str())
+            .and_then(|uri| Url::parse(uri).ok());
+        let Some(uri) = uri else {
+            self.client
+                .log_message(MessageType::ERROR, "Missing document URI")
+                .await;
+            return Ok(None);
+        };
+        let Some(document) = self.parsed_document(&uri).await else {
+            return Ok(None);
+        };
+
+        let lines = args
+            .get(1)
+            .and_then(|arg| arg.as_u64())
+            .zip(args.get(2).and_then(|arg| arg.as_u64()));
+        let requests = match lines {
+            Some((start, end)) => {
+                run::requests_in_range(&document.requests, start as usize, end as usize)
+            }
+            None => document.requests.iter().collect(),
+        };
+        if requests.is_empty() {
+            self.client
+                .show_message(MessageType::WARNING, "No requests to send")
+                .await;
+            return Ok(None);
+        }
+
+        let stop_on_failure = self.settings.lock().await.stop_on_failure;
+        let token = self
+            .begin_progress(token, format!("Sending {} requests", requests.len()))
+            .await;
+
+        let mut sections = Vec::new();
+        let mut results: Vec<run::RunResult> = Vec::new();
+        for (idx, request) in requests.iter().enumerate() {
+            if stop_on_failure && results.iter().any(run::RunResult::failed) {
+                results.push(run::RunResult::new(request, run::Outcome::Skipped));
+                continue;
+            }
+
+            self.report_progress(
+                &token,
+                format!("{} {}", request.method, request.url),
+                (idx * 100 / requests.len()) as u32,
+            )
+            .await;
+
+            let mut sent = Vec::new();
+            let result = self
+                .send_with_prerequisites(&uri, request, &document.requests, &mut sent)
+                .await;
+            for (resolved, response) in &sent {
+                if !resolved.has_directive(parser::NO_LOG) {
+                    sections.push(self.response_output(&uri, resolved, response));
+                }
+            }
+
+            let outcome = match result {
+                Ok(()) => sent.last().map_or(run::Outcome::Skipped, |(_, response)| {
+                    run::outcome(response)
+                }),
+                Err(e) => run::Outcome::Failed(format!("{:#}", e)),
+            };
+            results.push(run::RunResult::new(request, outcome));
+        }
+
+        let summary = run::summary(&results);
+        let headline = summary.lines().next().unwrap_or_default().to_string();
+        self.end_progress(&token, headline.clone()).await;
+
+        sections.push(format!("### SUMMARY ###\n{}\n", summary));
+        match self.write_output(&uri, &sections.join("\n")) {
+            Ok(()) => {
+                let kind = if results.iter().any(run::RunResult::failed) {
+                    MessageType::ERROR
+                } else {
+                    MessageType::INFO
+                };
+                self.client
+                    .show_message(
+                        kind,
+                        format!("{} - Responses appended to http-responses.http", headline),
+                    )
+                    .await;
+            }
+            Err(e) => {
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Failed to write responses: {}", e),
+                    )
+                    .await;
+            }
+        }
+
+        Ok(Some(serde_json::Value::String(summary)))
+    }
+
+    /// Sends `request`, first sending the named requests it references that have no response
+    /// yet. Each request sent is added to `sent` with its response, `request` itself last.
+    async fn send_with_prerequisites(
+        &self,
+        uri: &Url,
+        request: &parser::HttpRequest,
+        requests: &[parser::HttpRequest],
+        sent: &mut Vec<(parser::HttpRequest, executor::HttpResponse)>,
+    ) -> anyhow::Result<()> {
+        // Run named requests whose responses are referenced but not yet available, including
+        // references made inside `<@` body files
+        let document_dir = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.parent().map(|p| p.to_path_buf()));
+        let answered: HashSet<String> = self
+            .named_responses
+            .lock()
+            .await
+            .get(uri)
+            .map(|responses| responses.keys().cloned().collect())
+            .unwrap_or_default();
+        let target = Self::with_body_files(request, document_dir.as_deref()).await;
+        let reachable =
+            Self::reachable_requests(&target, requests, &answered, document_dir.as_deref()).await;
+
+        let prerequisites =
+            dependencies::plan_prerequisites(&target, &reachable, |name| answered.contains(name));
+        let prerequisites =
+            prerequisites.map_err(|e| anyhow::anyhow!("Cannot send request: {}", e))?;
+
+        for prerequisite in prerequisites {
+            let name = prerequisite.name.as_deref().unwrap_or_default();
+            self.client
+                .log_message(
+                    MessageType::INFO,
+                    format!("Running prerequisite request '{}'", name),
+                )
+                .await;
+
+            let exchange = self
+                .run_request(uri, prerequisite)
+                .await
+                .with_context(|| format!("Prerequisite request '{}' failed", name))?;
+            sent.push(exchange);
+        }
+
+        sent.push(self.run_request(uri, request).await?);
+        Ok(())
+    }
+
+    /// The named requests that `target` references without a response in `answered`, directly
+    /// or through other requests, with their `<@` body files read in. Only these files are read.
+    async fn reachable_requests(
+        target: &parser::HttpRequest,
+        requests: &[parser::HttpRequest],
+        answered: &HashSet<String>,
+        document_dir: Option<&std::path::Path>,
+    ) -> Vec<parser::HttpRequest> {
+        let mut reachable: Vec<parser::HttpRequest> = Vec::new();
+        let mut pending: Vec<String> = dependencies::referenced_requests(target)
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        while let Some(name) = pending.pop() {
+            let visited = reachable
+                .iter()
+                .any(|request| request.name.as_deref() == Some(name.as_str()));
+            if visited || answered.contains(&name) {
+                continue;
+            }
+            let Some(request) = requests
+                .iter()
+                .find(|request| request.name.as_deref() == Some(name.as_str()))
+            else {
+                continue;
+            };
+
+            let request = Self::with_body_files(request, document_dir).await;
+            pending.extend(
+                dependencies::referenced_requests(&request)
+                    .into_iter()
+                    .map(String::from),
+            );
+            reachable.push(request);
+        }
+
+        reachable
+    }
+
+    /// A copy of `request` with its `<@` body files read in, so that the references inside them
+    /// can be planned for. Files that cannot be read are left for sending to report.
+    async fn with_body_files(
+        request: &parser::HttpRequest,
+        document_dir: Option<&std::path::Path>,
+    ) -> parser::HttpRequest {
+        let mut with_files = request.clone();
+        match executor::load_body_files(&mut with_files, document_dir).await {
+            Ok(()) => with_files,
+            Err(_) => request.clone(),
+        }
+    }
+
+    /// Starts reporting progress under the client's token, or under a new one when the client
+    /// accepts server-initiated progress. Returns the token to report under.
+    async fn begin_progress(
+        &self,
+        token: Option<ProgressToken>,
+        title: String,
+    ) -> Option<ProgressToken> {
+        let token = match token {
+            Some(token) => token,
+            None => {
+                let token = ProgressToken::String(format!("http-lsp/{}", uuid::Uuid::new_v4()));
+                self.client
+                    .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                        token: token.clone(),
+                    })
+                    .await
+                    .ok()?;
+                token
+            }
+        };
+
+        self.send_progress(
+            &token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title,
+                cancellable: Some(false),
+                message: None,
+                percentage: Some(0),
+            }),
+        )
+        .await;
+        Some(token)
+    }
+
+    async fn report_progress(
+        &self,
+        token: &Option<ProgressToken>,
+        message: String,
+        percentage: u32,
+    ) {
+        if let Some(token) = token {
+            self.send_progress(
+                token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(false),
+                    message: Some(message),
+                    percentage: Some(percentage),
+                }),
+            )
+            .await;
+        }
+    }
+
+    async fn end_progress(&self, token: &Option<ProgressToken>, message: String) {
+        if let Some(token) = token {
+            self.send_progress(
+                token,
+                WorkDoneProgress::End(WorkDoneProgressEnd {
+                    message: Some(message),
+                }),
+            )
+            .await;
+        }
+    }
+
+    async fn send_progress(&self, token: &ProgressToken, progress: WorkDoneProgress) {
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            })
+            .await;
+    }
+
+    fn send_all_command(uri: &Url, count: usize) -> Command {
+        Command {
+            title: format!("▶ Send All {} Requests", count),
+            command: "http.sendAll".to_string(),
+            arguments: Some(vec![
+                serde_json::to_value(uri.to_string()).expect("Failed to serialize URI string")
+            ]),
+        }
+    }
+
+    /// Replaces the settings, keeping the current ones when the new ones are invalid.
+    /// Turning on cookie persistence loads the saved cookies.
+    async fn apply_settings(&self, value: &serde_json::Value) {
+        match Settings::from_value(value) {
+            Ok(settings) => {
+                let mut current = self.settings.lock().await;
+                let start_persisting = settings.persist_cookies && !current.persist_cookies;
+                *current = settings;
+                drop(current);
+
+                if start_persisting {
+                    self.load_cookies().await;
+                }
+            }
+            Err(e) => self.client.show_message(MessageType::WARNING, e).await,
+        }
+    }
+
+    /// A client for `profile`: a new one when connections must be fresh, otherwise a
+    /// long-lived one
+    async fn http_client(
+        &self,
+        profile: executor::ClientProfile,
+    ) -> anyhow::Result<reqwest::Client> {
+        if self.settings.lock().await.fresh_connections {
+            return executor::build_client(profile, &self.cookie_jar);
+        }
+
+        let mut clients = self.http_clients.lock().await;
+        if let Some(client) = clients.get(&profile) {
+            return Ok(client.clone());
+        }
+        let client = executor::build_client(profile, &self.cookie_jar)?;
+        clients.insert(profile, client.clone());
+        Ok(client)
+    }
+
+    /// The file cookies are persisted to, when persistence is on and there is a workspace
+    async fn cookie_file(&self) -> Option<PathBuf> {
+        if !self.settings.lock().await.persist_cookies {
+            return None;
+        }
+
+        self.workspace_root
+            .lock()
+            .await
+            .as_ref()
+            .map(|root| root.join(cookies::COOKIE_FILE))
+    }
+
+    /// Adds the persisted cookies to the jar, keeping cookies received since
+    async fn load_cookies(&self) {
+        let Some(path) = self.cookie_file().await else {
+            return;
+        };
+
+        let now = cookies::now();
+        match CookieJar::load(&path, now) {
+            Ok(saved) => {
+                let mut jar = self.cookie_jar.lock();
+                let received = std::mem::replace(&mut *jar, saved);
+                for cookie in received.cookies() {
+                    jar.store(cookie.clone(), now);
+                }
+            }
+            Err(e) => self.client.show_message(MessageType::WARNING, e).await,
+        }
+    }
+
+    /// Writes the jar to the cookie file when cookies are persisted
+    async fn save_cookies(&self) {
+        let Some(path) = self.cookie_file().await else {
+            return;
+        };
+
+        if let Err(e) = self.cookie_jar.lock().save(&path) {
+            Self::log_to_file(&e);
+        }
+    }
+
+    /// Resolves a request's variables, executes it and remembers its response if it is named.
+    /// Returns the resolved request alongside the response.
+    async fn run_request(
+        &self,
+        uri: &Url,
+        request: &parser::HttpRequest,
+    ) -> anyhow::Result<(parser::HttpRequest, executor::HttpResponse)> {
+        // Resolve system, response, file and environment variables fresh for every send
+        let environment = self
+            .load_environments_for_command(uri)
+            .await
+            .variables(self.active_environment.lock().await.as_deref());
+        let document_dir = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.parent().map(|p| p.to_path_buf()));
+        let file_variables = self
+            .parsed_document(uri)
+            .await
+            .map(|document| document.variables_at(request.line_number))
+            .unwrap_or_default();
+        let mut request = request.clone();
+        executor::load_body_files(&mut request, document_dir.as_deref()).await?;
+
+        let named_responses = self.named_responses.lock().await;
+        let resolved = variables::resolve_request(&request, |name| {
+            if let Some(result) = system_variables::evaluate(name, document_dir.as_deref()) {
+                return result.map(Some);
+            }
+
+            if let Some(reference) = response_variables::parse_reference(name) {
+                let response = named_responses
+                    .get(uri)
+                    .and_then(|responses| responses.get(reference.request_name))
+                    .ok_or_else(|| {
+                        format!("Request '{}' has not been sent yet", reference.request_name)
+                    })?;
+                return response_variables::resolve(&reference, response).map(Some);
+            }
+
+            // File variables are already substituted in the document, but not in `<@` files
+            if let Some(value) = file_variables.get(name) {
+                return Ok(Some(variables::substitute(value, &environment)));
+            }
+
+            Ok(environment.get(name).cloned())
+        });
+        drop(named_responses);
+
+        let request = resolved.map_err(|e| anyhow::anyhow!("Cannot send request: {}", e))?;
+
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!("Executing {} request to {}", request.method, request.url),
+            )
+            .await;
+
+        let profile = executor::ClientProfile::for_request(&request)?;
+        let client = self.http_client(profile).await?;
+        let response = executor::execute_request(&client, &request)
+            .await
+            .context("Request failed")?;
+        if profile.cookies {
+            self.save_cookies().await;
+        }
+
+        if let Some(name) = &request.name {
+            self.named_responses
+                .lock()
+                .await
+                .entry(uri.clone())
+                .or_default()
+                .insert(name.clone(), response.clone());
+        }
+
+        Ok((request, response))
+    }
+
+    /// Appends a request and its response to http-responses.http, unless it has `# @no-log`
+    fn append_response_output(
+        &self,
+        uri: &Url,
+        request: &parser::HttpRequest,
+        response: &executor::HttpResponse,
+    ) -> std::io::Result<()> {
+        if request.has_directive(parser::NO_LOG) {
+            return Ok(());
+        }
+        self.write_output(uri, &self.response_output(uri, request, response))
+    }
+
+    /// A request and its response as written to http-responses.http. Binary bodies are saved
+    /// to a file next to it, which the output links to.
+    fn response_output(
+        &self,
+        uri: &Url,
+        request: &parser::HttpRequest,
+        response: &executor::HttpResponse,
+    ) -> String {
+        let body_file = if response.is_text() {
+            None
+        } else {
+            Self::save_body(uri, response)
+                .inspect_err(|e| Self::log_to_file(&format!("Failed to save response body: {}", e)))
+                .ok()
+        };
+
+        self.format_response_output(request, response, body_file.as_deref())
+    }
+
+    /// Writes a response body to a new file in the output directory
+    fn save_body(uri: &Url, response: &executor::HttpResponse) -> std::io::Result<PathBuf> {
+        let file_name = format!(
+            "response-{}.{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S%3f"),
+            response_body::extension(&response.headers)
+        );
+        let path = Self::output_dir(uri).join(file_name);
+        std::fs::write(&path, &response.raw_body)?;
+        Ok(path)
+    }
+
+    /// Directory holding http-responses.http for a document
+    fn output_dir(uri: &Url) -> PathBuf {
+        if uri.path_segments().is_none() {
+            return std::env::temp_dir();
+        }
+
+        let path = uri.path();
+        // Find the project root by looking for common indicators
+        if let Some(pos) = path.rfind("/test/") {
+            PathBuf::from(&path[..pos])
+        } else if let Some(pos) = path.rfind("/src/") {
+            PathBuf::from(&path[..pos])
+        } else {
+            // Fallback: use parent directory or temp directory
+            Path::new(path)
+                .parent()
+                .map(|p| p.to_path_buf())
+                .unwrap_or_else(std::env::temp_dir)
+        }
+    }
+
+    /// Appends `content` to http-responses.http under a timestamped header
+    fn write_output(&self, uri: &Url, content: &str) -> std::io::Result<()> {
+        let output_file = Self::output_dir(uri).join("http-responses.http");
+        Self::log_to_file(&format!(
+            "Writing response to output file: {}",
+            output_file.display()
+        ));
+
+        // Prepare content with separator
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        let separator = "=".repeat(80);
+        let header = format!("{}\n[{}]\n{}\n", separator, timestamp, separator);
+        let full_content = format!("{}{}\n\n", header, content);
+
+        // Append to the output file
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&output_file)
+            .and_then(|mut file| file.write_all(full_content.as_bytes()));
+
+        match &result {
+            Ok(()) => Self::log_to_file("Response written successfully"),
+            Err(e) => Self::log_to_file(&format!("Failed to write to output file: {}", e)),
+        }
+
+        result
+    }
+
+    async fn switch_environment(
+        &self,
+        args: Vec<serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = args
+            .first()
+            .and_then(|arg| arg.as_str())
+            .and_then(|uri| Url::parse(uri).ok());
+        let Some(uri) = uri else {
+            self.client
+                .log_message(MessageType::ERROR, "Missing document URI")
+                .await;
+            return Ok(None);
+        };
+
+        let environments = self.load_environments_for_command(&uri).await;
+        let name = args.get(1).and_then(|arg| arg.as_str());
+
+        match name {
+            Some(name) if environments.contains(name) => {
+                *self.active_environment.lock().await = Some(name.to_string());
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!("Switched to '{}' environment", name),
+                    )
+                    .await;
+                Ok(Some(serde_json::Value::String(name.to_string())))
+            }
+            Some(name) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("Unknown environment: {}", name))
+                    .await;
+                Ok(None)
+            }
+            None => {
+                let active = self.active_environment.lock().await.clone();
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!(
+                            "Active environment: {}. Available: {}",
+                            active.as_deref().unwrap_or("none"),
+                            environments.names().join(", ")
+                        ),
+                    )
+                    .await;
+                Ok(None)
+            }
         }
+    }
+
+    /// Opens the request at the given line as a cURL command in a scratch file, and returns
+    /// the command for clients that don't support showing documents
+    async fn copy_as_curl(
+        &self,
+        args: Vec<serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>> {
+        let Some((uri, request)) = self.request_argument(&args).await else {
+            return Ok(None);
+        };
 
-        Ok(None)
+        let (request, variables) = self.prepare_export(&uri, request).await;
+        let command = curl::to_curl(&request, &variables);
+        self.open_scratch("request.curl.sh", &command).await;
+
+        Ok(Some(serde_json::Value::String(command)))
     }
 
-    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
-        let uri = params.text_document.uri;
+    /// Opens the cookies in the jar as JSON
+    async fn view_cookies(&self) -> Result<Option<serde_json::Value>> {
+        let cookies = serde_json::to_value(self.cookie_jar.lock().cookies())
+            .expect("Failed to serialize cookies");
+        let content = serde_json::to_string_pretty(&cookies).expect("Failed to serialize cookies");
+        self.open_scratch("cookies.json", &content).await;
 
-        Self::log_to_file(&format!("Code lens requested for: {}", uri));
+        Ok(Some(cookies))
+    }
 
+    /// Empties the jar, and the cookie file when cookies are persisted
+    async fn clear_cookies(&self) -> Result<Option<serde_json::Value>> {
+        let cleared = self.cookie_jar.lock().clear();
+        self.save_cookies().await;
         self.client
-            .log_message(
-                MessageType::INFO,
-                format!("Code lens requested for: {}", uri),
-            )
+            .show_message(MessageType::INFO, format!("Cleared {} cookies", cleared))
             .await;
 
-        let document_map = self.document_map.lock().await;
-        let content = match document_map.get(&uri) {
-            Some(content) => content.clone(),
-            None => {
-                self.client
-                    .log_message(
-                        MessageType::WARNING,
-                        format!("Document not found in map: {}", uri),
-                    )
-                    .await;
-                return Ok(None);
-            }
+        Ok(Some(serde_json::Value::from(cleared)))
+    }
+
+    /// Opens code sending the request at the given line with the given client library
+    async fn generate_code(
+        &self,
+        args: Vec<serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>> {
+        let language = args
+            .get(2)
+            .and_then(|arg| arg.as_str())
+            .and_then(snippets::Language::from_id);
+        let (Some((uri, request)), Some(language)) = (self.request_argument(&args).await, language)
+        else {
+            return Ok(None);
         };
-        drop(document_map);
 
-        let requests = parser::parse_http_file(&content);
+        let (request, variables) = self.prepare_export(&uri, request).await;
+        let code = snippets::generate(&request, &variables, language);
+        self.open_scratch(language.file_name(), &code).await;
 
-        Self::log_to_file(&format!("Found {} HTTP requests", requests.len()));
-        for req in &requests {
-            Self::log_to_file(&format!(
-                "  - {} {} at line {}",
-                req.method, req.url, req.line_number
-            ));
+        Ok(Some(serde_json::Value::String(code)))
+    }
+
+    /// `request` with its `<@` body files read in, so that their variables are substituted like
+    /// the rest, along with the file and environment variables to substitute when exporting it
+    async fn prepare_export(
+        &self,
+        uri: &Url,
+        request: parser::HttpRequest,
+    ) -> (parser::HttpRequest, HashMap<String, String>) {
+        let environment = self
+            .load_environments(uri)
+            .await
+            .variables(self.active_environment.lock().await.as_deref());
+        let file_variables = self
+            .parsed_document(uri)
+            .await
+            .map(|document| document.variables_at(request.line_number))
+            .unwrap_or_default();
+        let mut variables = environment.clone();
+        for (name, value) in file_variables {
+            variables.insert(name, variables::substitute(&value, &environment));
         }
 
-        self.client
-            .log_message(
-                MessageType::INFO,
-                format!("Found {} HTTP requests", requests.len()),
-            )
-            .await;
+        let document_dir = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.parent().map(|p| p.to_path_buf()));
+        let mut with_files = request.clone();
+        let request =
+            match executor::load_body_files(&mut with_files, document_dir.as_deref()).await {
+                Ok(()) => with_files,
+                Err(e) => {
+                    self.client
+                        .show_message(
+                            MessageType::WARNING,
+                            format!("{:#}, so its variables are not substituted", e),
+                        )
+                        .await;
+                    request
+                }
+            };
 
-        let mut lenses = Vec::new();
+        (request, variables)
+    }
 
-        for request in requests {
-            Self::log_to_file(&format!(
-                "Creating lens for {} at line {}",
-                request.method, request.line_number
-            ));
+    /// The request identified by a command's document URI and request line arguments
+    async fn request_argument(
+        &self,
+        args: &[serde_json::Value],
+    ) -> Option<(Url, parser::HttpRequest)> {
+        let uri = args
+            .first()
+            .and_then(|arg| arg.as_str())
+            .and_then(|uri| Url::parse(uri).ok());
+        let line_number = args.get(1).and_then(|arg| arg.as_u64());
+        let (Some(uri), Some(line_number)) = (uri, line_number) else {
+            self.client
+                .log_message(MessageType::ERROR, "Missing document URI or line")
+                .await;
+            return None;
+        };
 
-            // Create a code lens above the request line
-            let range = Range {
-                start: Position {
-                    line: request.line_number as u32,
-                    character: 0,
-                },
-                end: Position {
-                    line: request.line_number as u32,
-                    character: 0,
-                },
-            };
+        let document = self.parsed_document(&uri).await?;
+        let request = document
+            .requests
+            .iter()
+            .find(|r| r.line_number == line_number as usize)?
+            .clone();
+        Some((uri, request))
+    }
 
-            let lens = CodeLens {
-                range,
-                command: Some(Command {
-                    title: format!("▶ Send {} Request", request.method),
-                    command: "http.sendRequest".to_string(),
-                    arguments: Some(vec![
-                        serde_json::to_value(uri.to_string())
-                            .expect("Failed to serialize URI string"),
-                        serde_json::to_value(request.line_number)
-                            .expect("Failed to serialize line number"),
-                    ]),
-                }),
-                data: None,
-            };
+    /// Writes generated text to a scratch file and opens it, falling back to a message
+    /// when the client can't show documents
+    async fn open_scratch(&self, file_name: &str, content: &str) {
+        let scratch = std::env::temp_dir().join("http-lsp").join(file_name);
+        let written = std::fs::create_dir_all(scratch.parent().unwrap_or(&scratch))
+            .and_then(|()| std::fs::write(&scratch, format!("{}\n", content)));
+        let shown = match (written, Url::from_file_path(&scratch)) {
+            (Ok(()), Ok(scratch_uri)) => self
+                .client
+                .show_document(ShowDocumentParams {
+                    uri: scratch_uri,
+                    external: Some(false),
+                    take_focus: Some(true),
+                    selection: None,
+                })
+                .await
+                .unwrap_or(false),
+            (Err(e), _) => {
+                Self::log_to_file(&format!("Failed to write scratch file: {}", e));
+                false
+            }
+            (_, Err(())) => false,
+        };
 
-            lenses.push(lens);
+        if !shown {
+            self.client
+                .show_message(MessageType::INFO, content.to_string())
+                .await;
         }
+    }
 
-        Self::log_to_file(&format!("Returning {} code lenses", lenses.len()));
+    /// Directory holding the environment files: the workspace root, or the document's directory
+    async fn environment_dir(&self, uri: &Url) -> Option<PathBuf> {
+        if let Some(root) = self.workspace_root.lock().await.clone() {
+            return Some(root);
+        }
 
-        Ok(Some(lenses))
+        uri.to_file_path()
+            .ok()
+            .and_then(|path| path.parent().map(|p| p.to_path_buf()))
     }
 
-    async fn execute_command(
-        &self,
-        params: ExecuteCommandParams,
-    ) -> Result<Option<serde_json::Value>> {
-        if params.command == "http.sendRequest" {
-            // Extract arguments
-            let args = params.arguments;
-            if args.len() >= 2 {
-                let uri_str = args[0].as_str().unwrap_or("");
-                let line_number: usize = args[1].as_u64().unwrap_or(0) as usize;
-
-                let uri = match Url::parse(uri_str) {
-                    Ok(u) => u,
-                    Err(e) => {
-                        self.client
-                            .log_message(MessageType::ERROR, format!("Invalid URI: {}", e))
-                            .await;
-                        return Ok(None);
-                    }
-                };
+    /// Asks the client to report changes to environment files, falling back to reading them
+    /// on every use if it refuses
+    async fn watch_environment_files(&self) {
+        let watchers = [environment::PUBLIC_ENV_FILE, environment::PRIVATE_ENV_FILE]
+            .map(|file| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(format!("**/{}", file)),
+                kind: None,
+            })
+            .to_vec();
+        let registration = Registration {
+            id: "http-environment-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers,
+            })
+            .ok(),
+        };
 
-                let document_map = self.document_map.lock().await;
-                let content = match document_map.get(&uri) {
-                    Some(content) => content.clone(),
-                    None => {
-                        self.client
-                            .log_message(MessageType::ERROR, "Document not found")
-                            .await;
-                        return Ok(None);
-                    }
-                };
-                drop(document_map);
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            *self.watching_environment_files.lock().await = false;
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Could not watch environment files: {}", e),
+                )
+                .await;
+        }
+    }
 
-                // Parse requests and find the one at the specified line
-                let requests = parser::parse_http_file(&content);
-                let request = requests.iter().find(|r| r.line_number == line_number);
+    /// Environment files for a document, or none if they are malformed. Errors are logged when
+    /// the files are read rather than shown, as this runs on every hover and keystroke.
+    async fn load_environments(&self, uri: &Url) -> Arc<Environments> {
+        self.try_load_environments(uri).await.unwrap_or_default()
+    }
 
-                if let Some(request) = request {
-                    self.client
-                        .log_message(
-                            MessageType::INFO,
-                            format!("Executing {} request to {}", request.method, request.url),
-                        )
-                        .await;
+    /// Environment files for a command the user ran, showing them why they are malformed
+    async fn load_environments_for_command(&self, uri: &Url) -> Arc<Environments> {
+        match self.try_load_environments(uri).await {
+            Ok(environments) => environments,
+            Err(message) => {
+                self.client.show_message(MessageType::ERROR, message).await;
+                Arc::default()
+            }
+        }
+    }
 
-                    // Execute the request
-                    match executor::execute_request(request).await {
-                        Ok(response) => {
-                            // Create a formatted response document
-                            let response_content = self.format_response_output(request, &response);
-
-                            // Get the workspace root from the URI
-                            let workspace_root = if let Some(_segments) = uri.path_segments() {
-                                let path = uri.path();
-                                // Find the project root by looking for common indicators
-                                if let Some(pos) = path.rfind("/test/") {
-                                    std::path::PathBuf::from(&path[..pos])
-                                } else if let Some(pos) = path.rfind("/src/") {
-                                    std::path::PathBuf::from(&path[..pos])
-                                } else {
-                                    // Fallback: use parent directory or temp directory
-                                    std::path::Path::new(path)
-                                        .parent()
-                                        .map(|p| p.to_path_buf())
-                                        .unwrap_or_else(std::env::temp_dir)
-                                }
-                            } else {
-                                std::env::temp_dir()
-                            };
-
-                            let output_file = workspace_root.join("http-responses.http");
-                            Self::log_to_file(&format!(
-                                "Writing response to output file: {}",
-                                output_file.display()
-                            ));
-
-                            // Prepare content with separator
-                            let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-                            let separator = "=".repeat(80);
-                            let header = format!("{}\n[{}]\n{}\n", separator, timestamp, separator);
-                            let full_content = format!("{}{}\n\n", header, response_content);
-
-                            // Append to the output file
-                            use std::io::Write;
-                            match OpenOptions::new()
-                                .create(true)
-                                .append(true)
-                                .open(&output_file)
-                            {
-                                Ok(mut file) => {
-                                    if let Err(e) = file.write_all(full_content.as_bytes()) {
-                                        Self::log_to_file(&format!(
-                                            "Failed to write to output file: {}",
-                                            e
-                                        ));
-                                    } else {
-                                        Self::log_to_file("Response written successfully");
-
-                                        // Show success message with file location
-                                        self.client
-                                            .show_message(
-                                                MessageType::INFO,
-                                                format!("✓ {} - Response appended to http-responses.http", response.summary()),
-                                            )
-                                            .await;
-                                    }
-                                }
-                                Err(e) => {
-                                    Self::log_to_file(&format!(
-                                        "Failed to open output file: {}",
-                                        e
-                                    ));
-
-                                    self.client
-                                        .show_message(
-                                            MessageType::ERROR,
-                                            format!("Failed to write response: {}", e),
-                                        )
-                                        .await;
-                                }
-                            }
-
-                            return Ok(Some(
-                                serde_json::to_value(response.summary())
-                                    .expect("Failed to serialize response summary"),
-                            ));
-                        }
-                        Err(e) => {
-                            self.client
-                                .show_message(MessageType::ERROR, format!("Request failed: {}", e))
-                                .await;
-                        }
-                    }
-                }
+    /// Reads and caches the environment files of a document's directory, or returns the
+    /// cached result while the client is watching them
+    async fn try_load_environments(&self, uri: &Url) -> LoadedEnvironments {
+        let Some(dir) = self.environment_dir(uri).await else {
+            return Ok(Arc::default());
+        };
+        if let Some(cached) = self.environments.lock().await.get(&dir) {
+            return cached.clone();
+        }
+
+        let loaded = Environments::load(&dir)
+            .map(Arc::new)
+            .map_err(|e| format!("Failed to load environments: {:#}", e));
+        if let Err(message) = &loaded {
+            self.client.log_message(MessageType::ERROR, message).await;
+        }
+
+        if *self.watching_environment_files.lock().await {
+            self.environments.lock().await.insert(dir, loaded.clone());
+        }
+        loaded
+    }
+
+    async fn document_text(&self, uri: &Url) -> Option<String> {
+        let document_map = self.document_map.lock().await;
+        document_map
+            .get(uri)
+            .map(|document| document.text().to_string())
+    }
+
+    /// Keys defining `name` in the environment files, as `(file, environment, location)`
+    async fn environment_key_locations(
+        &self,
+        uri: &Url,
+        name: &str,
+    ) -> Vec<(&'static str, String, Location)> {
+        let Some(dir) = self.environment_dir(uri).await else {
+            return Vec::new();
+        };
+
+        let mut locations = Vec::new();
+        for file in [environment::PUBLIC_ENV_FILE, environment::PRIVATE_ENV_FILE] {
+            let path = dir.join(file);
+            let (Ok(content), Ok(file_uri)) =
+                (std::fs::read_to_string(&path), Url::from_file_path(&path))
+            else {
+                continue;
+            };
+
+            for (environment, range) in navigation::environment_keys(&content, name) {
+                locations.push((
+                    file,
+                    environment.to_string(),
+                    Location::new(file_uri.clone(), range),
+                ));
             }
         }
 
-        Ok(None)
+        locations
     }
-}
 
-impl HttpLspServer {
+    /// Cached parse of an open document
+    async fn parsed_document(&self, uri: &Url) -> Option<Arc<parser::ParsedDocument>> {
+        let mut document_map = self.document_map.lock().await;
+        document_map.get_mut(uri).map(Document::parsed)
+    }
+
+    /// Converts the document's parse errors into LSP diagnostics
+    fn diagnostics(document: &parser::ParsedDocument) -> Vec<Diagnostic> {
+        document
+            .errors
+            .iter()
+            .map(|error| Diagnostic {
+                range: Range {
+                    start: Position {
+                        line: error.line as u32,
+                        character: error.start_column as u32,
+                    },
+                    end: Position {
+                        line: error.line as u32,
+                        character: error.end_column as u32,
+                    },
+                },
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("http-lsp".to_string()),
+                message: error.message.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Renders a request and its response. Binary bodies are shown as a hex preview, with a
+    /// link to `body_file` when they were saved.
     fn format_response_output(
         &self,
         request: &parser::HttpRequest,
         response: &executor::HttpResponse,
+        body_file: Option<&Path>,
     ) -> String {
         let mut output = String::new();
 
@@ -432,6 +1872,9 @@ impl HttpLspServer {
             output.push('\n');
             output.push_str(body);
             output.push('\n');
+        } else if let Some(body_file) = &request.body_file {
+            output.push('\n');
+            output.push_str(&format!("< {}\n", body_file.path.display()));
         }
 
         output.push('\n');
@@ -452,21 +1895,37 @@ impl HttpLspServer {
         // Response body
         output.push('\n');
 
-        // Try to pretty-print JSON
-        if let Some(content_type) = response.headers.get("content-type") {
-            if content_type.contains("application/json") {
-                if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&response.body) {
-                    if let Ok(pretty) = serde_json::to_string_pretty(&json_value) {
-                        output.push_str(&pretty);
-                        output.push('\n');
-                        return output;
-                    }
-                }
+        if response.is_text() {
+            // Pretty-print JSON, otherwise add the body as-is
+            let pretty_json = response
+                .headers
+                .get("content-type")
+                .filter(|content_type| content_type.contains("application/json"))
+                .and_then(|_| serde_json::from_str::<serde_json::Value>(&response.body).ok())
+                .and_then(|json_value| serde_json::to_string_pretty(&json_value).ok());
+            output.push_str(pretty_json.as_deref().unwrap_or(&response.body));
+        } else {
+            let description = format!(
+                "# Binary body ({}, {})",
+                response
+                    .headers
+                    .get("content-type")
+                    .map_or("no content type", String::as_str),
+                response_body::format_size(response.raw_body.len())
+            );
+            let link = body_file.and_then(|path| Url::from_file_path(path).ok());
+            match link {
+                Some(link) => output.push_str(&format!("{} saved to {}\n", description, link)),
+                None => output.push_str(&format!("{}, not saved\n", description)),
             }
+            output.push_str(&response_body::hex_preview(&response.raw_body));
         }
+        output.push('\n');
 
-        // Default: just add the body as-is
-        output.push_str(&response.body);
+        // Waterfall of where the time went
+        output.push('\n');
+        output.push_str("### TIMING ###\n");
+        output.push_str(&response.timings.waterfall());
         output.push('\n');
 
         output
@@ -476,6 +1935,8 @@ impl HttpLspServer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
     use rstest::rstest;
 
     fn create_test_client() -> Client {
@@ -494,14 +1955,21 @@ mod tests {
         parser::HttpRequest {
             method: method.to_string(),
             url: url.to_string(),
-            headers: HashMap::new(),
+            headers: Headers::new(),
             body: None,
+            body_file: None,
+            multipart: None,
             line_number: 0,
+            name: None,
+            title: None,
+            block_lines: 0..1,
+            body_lines: None,
+            directives: HashMap::new(),
         }
     }
 
     fn create_test_response(status: u16, status_text: &str, body: &str) -> executor::HttpResponse {
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         headers.insert("content-type".to_string(), "application/json".to_string());
 
         executor::HttpResponse {
@@ -509,7 +1977,9 @@ mod tests {
             status_text: status_text.to_string(),
             headers,
             body: body.to_string(),
+            raw_body: body.into(),
             duration_ms: 100,
+            timings: Timings::default(),
         }
     }
 
@@ -519,7 +1989,7 @@ mod tests {
         let request = create_test_request("GET", "http://example.com/api");
         let response = create_test_response(200, "OK", r#"{"message": "success"}"#);
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains("### REQUEST ###"));
         assert!(output.contains("GET http://example.com/api"));
@@ -531,12 +2001,31 @@ mod tests {
         let request = create_test_request("GET", "http://example.com/api");
         let response = create_test_response(200, "OK", r#"{"message": "success"}"#);
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains("### RESPONSE ###"));
         assert!(output.contains("HTTP/1.1 200 OK (100ms)"));
     }
 
+    #[test]
+    fn test_format_response_output_ends_with_timing_waterfall() {
+        let server = create_test_server();
+        let request = create_test_request("GET", "http://example.com/api");
+        let mut response = create_test_response(200, "OK", r#"{"message": "success"}"#);
+        response.timings = Timings {
+            connect: Some(std::time::Duration::from_millis(5)),
+            first_byte: std::time::Duration::from_millis(15),
+            ..Timings::default()
+        };
+
+        let output = server.format_response_output(&request, &response, None);
+
+        assert!(output.ends_with(&format!(
+            "}}\n\n### TIMING ###\n{}\n",
+            response.timings.waterfall()
+        )));
+    }
+
     #[test]
     fn test_format_response_output_with_request_headers() {
         let server = create_test_server();
@@ -550,7 +2039,7 @@ mod tests {
 
         let response = create_test_response(200, "OK", r#"{"message": "success"}"#);
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains("Authorization: Bearer token"));
         assert!(output.contains("Accept: application/json"));
@@ -564,7 +2053,7 @@ mod tests {
 
         let response = create_test_response(201, "Created", r#"{"id": 123}"#);
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains(r#"{"name": "test"}"#));
     }
@@ -577,7 +2066,7 @@ mod tests {
         let json_body = r#"{"name":"test","value":123}"#;
         let response = create_test_response(200, "OK", json_body);
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         // Pretty-printed JSON should have newlines and indentation
         assert!(output.contains("\"name\""));
@@ -589,7 +2078,7 @@ mod tests {
         let server = create_test_server();
         let request = create_test_request("GET", "http://example.com/api");
 
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         headers.insert("content-type".to_string(), "text/plain".to_string());
 
         let response = executor::HttpResponse {
@@ -597,15 +2086,86 @@ mod tests {
             status_text: "OK".to_string(),
             headers,
             body: "Plain text response".to_string(),
+            raw_body: "Plain text response".into(),
             duration_ms: 50,
+            timings: Timings::default(),
         };
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains("Plain text response"));
         assert!(output.contains("content-type: text/plain"));
     }
 
+    fn create_binary_response() -> executor::HttpResponse {
+        let mut headers = Headers::new();
+        headers.insert("content-type".to_string(), "image/png".to_string());
+        let raw_body = b"\x89PNG\r\n\x1a\n".to_vec();
+
+        executor::HttpResponse {
+            status: 200,
+            status_text: "OK".to_string(),
+            headers,
+            body: String::from_utf8_lossy(&raw_body).into_owned(),
+            raw_body,
+            duration_ms: 50,
+            timings: Timings::default(),
+        }
+    }
+
+    #[test]
+    fn test_format_response_output_links_saved_binary_body() {
+        let server = create_test_server();
+        let request = create_test_request("GET", "http://example.com/logo.png");
+        let response = create_binary_response();
+        let body_file = std::env::temp_dir().join("response.png");
+
+        let output = server.format_response_output(&request, &response, Some(&body_file));
+
+        let link = Url::from_file_path(&body_file).expect("Temp dir should be absolute");
+        assert!(output.contains(&format!(
+            "# Binary body (image/png, 8 bytes) saved to {}",
+            link
+        )));
+        assert!(output.contains("00000000  89 50 4e 47 0d 0a 1a 0a"));
+        assert!(!output.contains(&response.body));
+    }
+
+    #[test]
+    fn test_format_response_output_reports_unsaved_binary_body() {
+        let server = create_test_server();
+        let request = create_test_request("GET", "http://example.com/logo.png");
+
+        let output = server.format_response_output(&request, &create_binary_response(), None);
+
+        assert!(output.contains("# Binary body (image/png, 8 bytes), not saved"));
+    }
+
+    #[test]
+    fn test_response_output_saves_binary_body_next_to_output() {
+        let server = create_test_server();
+        let dir = std::env::temp_dir().join(format!("http-lsp-body-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Temp dir should be absolute");
+        let request = create_test_request("GET", "http://example.com/logo.png");
+        let response = create_binary_response();
+
+        let output = server.response_output(&uri, &request, &response);
+
+        let saved: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .expect("Failed to read temp dir")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].extension().and_then(|e| e.to_str()), Some("png"));
+        assert!(output.contains(
+            Url::from_file_path(&saved[0])
+                .expect("Saved path should be absolute")
+                .as_str()
+        ));
+    }
+
     #[rstest]
     #[case("GET", 200, "OK")]
     #[case("POST", 201, "Created")]
@@ -621,7 +2181,7 @@ mod tests {
         let request = create_test_request(method, "http://example.com/api");
         let response = create_test_response(status, status_text, "{}");
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains(&format!("{} http://example.com/api", method)));
         assert!(output.contains(&format!("HTTP/1.1 {} {}", status, status_text)));
@@ -633,7 +2193,7 @@ mod tests {
         let request = create_test_request("GET", "http://example.com/api");
         let response = create_test_response(200, "OK", r#"{"data": "test"}"#);
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         // Verify the structure
         let request_pos = output
@@ -658,7 +2218,7 @@ mod tests {
         let server = create_test_server();
         let request = create_test_request("DELETE", "http://example.com/api/1");
 
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         headers.insert("content-length".to_string(), "0".to_string());
 
         let response = executor::HttpResponse {
@@ -666,13 +2226,352 @@ mod tests {
             status_text: "No Content".to_string(),
             headers,
             body: String::new(),
+            raw_body: Vec::new(),
             duration_ms: 75,
+            timings: Timings::default(),
         };
 
-        let output = server.format_response_output(&request, &response);
+        let output = server.format_response_output(&request, &response, None);
 
         assert!(output.contains("HTTP/1.1 204 No Content (75ms)"));
         assert!(output.contains("### REQUEST ###"));
         assert!(output.contains("### RESPONSE ###"));
     }
+
+    #[tokio::test]
+    async fn test_environment_dir_prefers_workspace_root() {
+        let server = create_test_server();
+        let document_dir = std::env::temp_dir();
+        let uri = Url::from_file_path(document_dir.join("api.http")).expect("Valid file URI");
+
+        assert_eq!(server.environment_dir(&uri).await, Some(document_dir));
+
+        let root = PathBuf::from("project");
+        *server.workspace_root.lock().await = Some(root.clone());
+        assert_eq!(server.environment_dir(&uri).await, Some(root));
+    }
+
+    fn temp_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("http-lsp-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+        dir
+    }
+
+    #[rstest]
+    #[case(true, vec!["dev"])]
+    #[case(false, vec!["prod"])]
+    #[tokio::test]
+    async fn test_environments_are_cached_only_while_watched(
+        #[case] watching: bool,
+        #[case] expected: Vec<&str>,
+    ) {
+        let server = create_test_server();
+        *server.watching_environment_files.lock().await = watching;
+        let dir = temp_test_dir(&format!("env-cache-{}", watching));
+        let env_file = dir.join(environment::PUBLIC_ENV_FILE);
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+
+        std::fs::write(&env_file, r#"{"dev": {}}"#).expect("Failed to write env file");
+        assert_eq!(server.load_environments(&uri).await.names(), vec!["dev"]);
+
+        std::fs::write(&env_file, r#"{"prod": {}}"#).expect("Failed to write env file");
+        let names = server.load_environments(&uri).await.names().join(",");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert_eq!(names, expected.join(","));
+    }
+
+    #[tokio::test]
+    async fn test_named_responses_are_scoped_to_their_document() {
+        let server = create_test_server();
+        let a = Url::parse("file:///project/a.http").expect("Valid file URI");
+        let b = Url::parse("file:///project/b.http").expect("Valid file URI");
+        server
+            .named_responses
+            .lock()
+            .await
+            .entry(a)
+            .or_default()
+            .insert("login".to_string(), create_test_response(200, "OK", "{}"));
+
+        let requests =
+            parser::parse_http_file("GET http://127.0.0.1:9/{{login.response.body.$.id}}");
+        let error = server
+            .send_with_prerequisites(&b, &requests[0], &requests, &mut Vec::new())
+            .await
+            .expect_err("Response from another document should not be used");
+
+        assert!(error
+            .to_string()
+            .contains("Request 'login' is not defined in this document"));
+    }
+
+    #[tokio::test]
+    async fn test_prerequisites_include_references_in_body_files() {
+        let server = create_test_server();
+        let dir = temp_test_dir("body-file-references");
+        std::fs::write(
+            dir.join("body.json"),
+            r#"{"token": "{{login.response.body}}"}"#,
+        )
+        .expect("Failed to write body file");
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+
+        let requests = parser::parse_http_file("POST http://127.0.0.1:9/\n\n<@ body.json");
+        let error = server
+            .send_with_prerequisites(&uri, &requests[0], &requests, &mut Vec::new())
+            .await
+            .expect_err("Reference to an undefined request should fail");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert!(error
+            .to_string()
+            .contains("Request 'login' is not defined in this document"));
+    }
+
+    #[tokio::test]
+    async fn test_prerequisites_follow_body_files_of_referenced_requests() {
+        let server = create_test_server();
+        let dir = temp_test_dir("chained-body-file-references");
+        std::fs::write(
+            dir.join("token.json"),
+            r#"{"user": "{{login.response.body}}"}"#,
+        )
+        .expect("Failed to write body file");
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+
+        let requests = parser::parse_http_file(
+            "GET http://127.0.0.1:9/{{token.response.body}}\n\n\
+             ###\n# @name token\nPOST http://127.0.0.1:9/\n\n<@ token.json\n\n\
+             ###\nPOST http://127.0.0.1:9/\n\n<@ missing.json",
+        );
+        let error = server
+            .send_with_prerequisites(&uri, &requests[0], &requests, &mut Vec::new())
+            .await
+            .expect_err("Reference to an undefined request should fail");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert!(error
+            .to_string()
+            .contains("Request 'login' is not defined in this document"));
+    }
+
+    #[tokio::test]
+    async fn test_run_request_substitutes_file_variables_in_body_files() {
+        let server = create_test_server();
+        let dir = temp_test_dir("file-variables-body-file");
+        std::fs::write(dir.join("body.json"), r#"{"id": "{{id}}"}"#)
+            .expect("Failed to write body file");
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+        let (url, received) =
+            executor::tests::serve_once("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let content = format!("@id = 42\n\nPOST {}\n\n<@ body.json", url);
+        server
+            .document_map
+            .lock()
+            .await
+            .insert(uri.clone(), Document::new(content.clone(), 1));
+
+        let requests = parser::parse_http_file(&content);
+        let result = server.run_request(&uri, &requests[0]).await;
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+        result.expect("Request should be sent");
+        let received = received.await.expect("Server task failed");
+
+        assert!(String::from_utf8_lossy(&received).ends_with(r#"{"id": "42"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_run_request_substitutes_file_variables_in_multipart_files() {
+        let server = create_test_server();
+        let dir = temp_test_dir("file-variables-multipart-file");
+        std::fs::write(dir.join("meta.json"), r#"{"id": "{{id}}"}"#)
+            .expect("Failed to write part file");
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+        let (url, received) =
+            executor::tests::serve_once("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let content = format!(
+            "@id = 42\n\nPOST {}\nContent-Type: multipart/form-data; boundary=XYZ\n\n\
+             --XYZ\nContent-Disposition: form-data; name=\"meta\"\n\n<@ ./meta.json\n--XYZ--",
+            url
+        );
+        server
+            .document_map
+            .lock()
+            .await
+            .insert(uri.clone(), Document::new(content.clone(), 1));
+
+        let requests = parser::parse_http_file(&content);
+        let result = server.run_request(&uri, &requests[0]).await;
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+        result.expect("Request should be sent");
+        let received = received.await.expect("Server task failed");
+
+        assert!(String::from_utf8_lossy(&received).contains("\r\n\r\n{\"id\": \"42\"}\r\n--XYZ--"));
+    }
+
+    #[tokio::test]
+    async fn test_copy_as_curl_substitutes_variables_in_body_files() {
+        let server = create_test_server();
+        let dir = temp_test_dir("curl-body-file");
+        std::fs::write(
+            dir.join("body.json"),
+            r#"{"id": "{{id}}", "user": "{{user}}"}"#,
+        )
+        .expect("Failed to write body file");
+        std::fs::write(
+            dir.join(environment::PUBLIC_ENV_FILE),
+            r#"{"dev": {"id": "7", "user": "env"}}"#,
+        )
+        .expect("Failed to write env file");
+        *server.active_environment.lock().await = Some("dev".to_string());
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+        server.document_map.lock().await.insert(
+            uri.clone(),
+            Document::new(
+                "@user = {{id}}-ada\n\nPOST https://example.com\n\n<@ body.json".to_string(),
+                1,
+            ),
+        );
+
+        let command = server
+            .copy_as_curl(vec![
+                serde_json::Value::String(uri.to_string()),
+                serde_json::Value::from(2),
+            ])
+            .await
+            .expect("Command should succeed");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert_eq!(
+            command,
+            Some(serde_json::Value::String(
+                "curl -X POST 'https://example.com' \\\n  \
+                 --data-raw '{\"id\": \"7\", \"user\": \"7-ada\"}'"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_code_substitutes_variables_in_body_files() {
+        let server = create_test_server();
+        let dir = temp_test_dir("snippet-body-file");
+        std::fs::write(dir.join("body.txt"), "user {{user}}").expect("Failed to write body file");
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+        server.document_map.lock().await.insert(
+            uri.clone(),
+            Document::new(
+                "@user = ada\n\nPOST https://example.com\n\n<@ body.txt".to_string(),
+                1,
+            ),
+        );
+
+        let code = server
+            .generate_code(vec![
+                serde_json::Value::String(uri.to_string()),
+                serde_json::Value::from(2),
+                serde_json::Value::String("python".to_string()),
+            ])
+            .await
+            .expect("Command should succeed");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        let code = code.and_then(|code| code.as_str().map(String::from));
+        assert!(code.expect("Expected code").contains("data=\"user ada\""));
+    }
+
+    #[tokio::test]
+    async fn test_watched_file_changes_reload_environments() {
+        let server = create_test_server();
+        *server.watching_environment_files.lock().await = true;
+        let dir = temp_test_dir("env-watch");
+        let env_file = dir.join(environment::PRIVATE_ENV_FILE);
+        let uri = Url::from_file_path(dir.join("api.http")).expect("Valid file URI");
+
+        std::fs::write(&env_file, "{").expect("Failed to write env file");
+        assert!(server.load_environments(&uri).await.names().is_empty());
+
+        std::fs::write(&env_file, r#"{"dev": {}}"#).expect("Failed to write env file");
+        assert!(server.load_environments(&uri).await.names().is_empty());
+
+        server
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent::new(
+                    Url::from_file_path(&env_file).expect("Valid file URI"),
+                    FileChangeType::CHANGED,
+                )],
+            })
+            .await;
+        let names = server.load_environments(&uri).await.names().join(",");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert_eq!(names, "dev");
+    }
+
+    #[test]
+    fn test_format_response_output_keeps_header_order_and_duplicates() {
+        let server = create_test_server();
+        let mut request = create_test_request("GET", "http://example.com/api");
+        request.headers.append("Accept", "application/json");
+        request.headers.append("Accept", "text/plain");
+
+        let mut response = create_test_response(200, "OK", "{}");
+        response.headers.append("Set-Cookie", "a=1");
+        response.headers.append("Set-Cookie", "b=2");
+
+        let output = server.format_response_output(&request, &response, None);
+
+        let json_accept = output
+            .find("Accept: application/json")
+            .expect("First Accept header not found");
+        let text_accept = output
+            .find("Accept: text/plain")
+            .expect("Second Accept header not found");
+        assert!(json_accept < text_accept);
+
+        let first_cookie = output.find("Set-Cookie: a=1").expect("a=1 not found");
+        let second_cookie = output.find("Set-Cookie: b=2").expect("b=2 not found");
+        assert!(first_cookie < second_cookie);
+    }
+
+    #[test]
+    fn test_format_response_output_with_body_file() {
+        let server = create_test_server();
+        let mut request = create_test_request("POST", "http://example.com/api");
+        request.body_file = Some(parser::BodyFile {
+            path: PathBuf::from("fixtures/upload.bin"),
+            substitute_variables: false,
+        });
+
+        let response = create_test_response(201, "Created", "{}");
+        let output = server.format_response_output(&request, &response, None);
+
+        assert!(output.contains(&format!(
+            "< {}",
+            PathBuf::from("fixtures/upload.bin").display()
+        )));
+    }
+
+    #[test]
+    fn test_diagnostics_from_parse_errors() {
+        let content =
+            "GET https://example.com\nAccept application/json\n###\nFETCH https://example.com";
+
+        let diagnostics = HttpLspServer::diagnostics(&parser::parse_document(content));
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 0));
+        assert_eq!(diagnostics[0].range.end, Position::new(1, 23));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[1].range.start, Position::new(3, 0));
+        assert!(diagnostics[1].message.contains("FETCH"));
+    }
+
+    #[test]
+    fn test_diagnostics_empty_for_valid_document() {
+        let content = "GET https://example.com\nAccept: application/json";
+
+        assert!(HttpLspServer::diagnostics(&parser::parse_document(content)).is_empty());
+    }
 }
@@ -1,6 +1,28 @@
+mod completion;
+mod cookies;
+mod curl;
+mod dependencies;
+mod document;
+mod environment;
 mod executor;
+mod folding;
+mod formatting;
+mod headers;
+mod hover;
 mod lsp_server;
+mod multipart;
+mod navigation;
 mod parser;
+mod response_body;
+mod response_variables;
+mod run;
+mod semantic_tokens;
+mod settings;
+mod snippets;
+mod symbols;
+mod system_variables;
+mod timing;
+mod variables;
 
 use tower_lsp::{LspService, Server};
 
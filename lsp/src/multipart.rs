@@ -0,0 +1,255 @@
+use crate::headers::Headers;
+use crate::parser::{self, BodyFile};
+use anyhow::{Context, Result};
+
+/// A `multipart/form-data` body split into its boundary-delimited parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Multipart {
+    pub boundary: String,
+    pub parts: Vec<MultipartPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartPart {
+    pub headers: Headers,
+    pub content: PartContent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartContent {
+    Text(String),
+    /// Contents read from a file with `< ./path` or `<@ ./path`
+    File(BodyFile),
+}
+
+/// Returns the boundary of a `multipart/*` Content-Type, or `None` for other types
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let (mime, _) = content_type.split_once(';')?;
+    if !mime.trim().to_ascii_lowercase().starts_with("multipart/") {
+        return None;
+    }
+
+    header_param(content_type, "boundary").filter(|b| !b.is_empty())
+}
+
+/// Finds `param=value` or `param="value"` among the `;`-separated parameters of a header
+pub fn header_param<'a>(header: &'a str, param: &str) -> Option<&'a str> {
+    header.split(';').skip(1).find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case(param) {
+            return None;
+        }
+
+        let value = value.trim();
+        Some(
+            value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value),
+        )
+    })
+}
+
+impl Multipart {
+    /// Splits a body on `--boundary` lines. Returns `None` if the body has no delimiter.
+    pub fn parse(body: &str, boundary: &str) -> Option<Self> {
+        let delimiter = format!("--{}", boundary);
+        let close_delimiter = format!("--{}--", boundary);
+
+        let mut parts = Vec::new();
+        let mut current: Option<Vec<&str>> = None;
+
+        for line in body.lines() {
+            let trimmed = line.trim_end();
+            if trimmed == delimiter || trimmed == close_delimiter {
+                if let Some(lines) = current.take() {
+                    parts.push(parse_part(&lines));
+                }
+                if trimmed == close_delimiter {
+                    break;
+                }
+                current = Some(Vec::new());
+            } else if let Some(lines) = &mut current {
+                lines.push(line);
+            }
+        }
+
+        // Tolerate a missing closing delimiter
+        if let Some(lines) = current {
+            parts.push(parse_part(&lines));
+        }
+
+        (!parts.is_empty()).then(|| Multipart {
+            boundary: boundary.to_string(),
+            parts,
+        })
+    }
+
+    /// Encodes the parts as a multipart body, reading file parts as binary
+    pub async fn encode(&self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+
+        for part in &self.parts {
+            body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+            for (name, value) in &part.headers {
+                body.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+
+            match &part.content {
+                PartContent::Text(text) => body.extend_from_slice(text.as_bytes()),
+                PartContent::File(file) => {
+                    let contents = tokio::fs::read(&file.path).await.with_context(|| {
+                        format!("Failed to read multipart file {}", file.path.display())
+                    })?;
+                    body.extend_from_slice(&contents);
+                }
+            }
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        Ok(body)
+    }
+}
+
+fn parse_part(lines: &[&str]) -> MultipartPart {
+    let mut headers = Headers::new();
+    let mut content_start = lines.len();
+
+    for (idx, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            content_start = idx + 1;
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.append(name.trim(), value.trim());
+        }
+    }
+
+    let text = lines[content_start.min(lines.len())..].join("\n");
+    let content = match parser::parse_body_file_reference(text.trim()) {
+        Some(file) => PartContent::File(file),
+        None => PartContent::Text(text),
+    };
+
+    MultipartPart { headers, content }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    const BODY: &str = r#"--WebKitBoundary
+Content-Disposition: form-data; name="title"
+
+Holiday photos
+--WebKitBoundary
+Content-Disposition: form-data; name="photo"; filename="beach.png"
+Content-Type: image/png
+
+< ./images/beach.png
+--WebKitBoundary--"#;
+
+    #[rstest]
+    #[case("multipart/form-data; boundary=WebKitBoundary", Some("WebKitBoundary"))]
+    #[case(
+        "multipart/form-data; boundary=\"quoted boundary\"",
+        Some("quoted boundary")
+    )]
+    #[case("Multipart/Mixed; charset=utf-8; Boundary=abc", Some("abc"))]
+    #[case("multipart/form-data", None)]
+    #[case("multipart/form-data; boundary=", None)]
+    #[case("application/json; boundary=abc", None)]
+    fn test_boundary(#[case] content_type: &str, #[case] expected: Option<&str>) {
+        assert_eq!(boundary(content_type), expected);
+    }
+
+    #[test]
+    fn test_parse_text_and_file_parts() {
+        let multipart = Multipart::parse(BODY, "WebKitBoundary").expect("Expected parts");
+
+        assert_eq!(multipart.parts.len(), 2);
+        assert_eq!(
+            multipart.parts[0].headers.get("content-disposition"),
+            Some(&"form-data; name=\"title\"".to_string())
+        );
+        assert_eq!(
+            multipart.parts[0].content,
+            PartContent::Text("Holiday photos".to_string())
+        );
+
+        let disposition = multipart.parts[1].headers.get("content-disposition");
+        assert_eq!(
+            disposition.and_then(|d| header_param(d, "filename")),
+            Some("beach.png")
+        );
+        assert_eq!(
+            multipart.parts[1].headers.get("content-type"),
+            Some(&"image/png".to_string())
+        );
+        assert_eq!(
+            multipart.parts[1].content,
+            PartContent::File(BodyFile {
+                path: PathBuf::from("./images/beach.png"),
+                substitute_variables: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_without_closing_delimiter() {
+        let body = "--b\nContent-Disposition: form-data; name=\"a\"\n\none\ntwo";
+        let multipart = Multipart::parse(body, "b").expect("Expected parts");
+
+        assert_eq!(multipart.parts.len(), 1);
+        assert_eq!(
+            multipart.parts[0].content,
+            PartContent::Text("one\ntwo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_without_delimiters() {
+        assert!(Multipart::parse("just text", "b").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_encode_includes_binary_file_contents() {
+        let path = std::env::temp_dir().join(format!("http-lsp-part-{}.bin", std::process::id()));
+        std::fs::write(&path, [0u8, 159, 146, 150]).expect("Failed to write part file");
+
+        let mut multipart = Multipart::parse(BODY, "WebKitBoundary").expect("Expected parts");
+        multipart.parts[1].content = PartContent::File(BodyFile {
+            path: path.clone(),
+            substitute_variables: false,
+        });
+
+        let encoded = multipart.encode().await;
+        std::fs::remove_file(&path).expect("Failed to remove part file");
+
+        let mut expected = b"--WebKitBoundary\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\
+\r\n\
+Holiday photos\r\n\
+--WebKitBoundary\r\n\
+Content-Disposition: form-data; name=\"photo\"; filename=\"beach.png\"\r\n\
+Content-Type: image/png\r\n\
+\r\n"
+            .to_vec();
+        expected.extend_from_slice(&[0, 159, 146, 150]);
+        expected.extend_from_slice(b"\r\n--WebKitBoundary--\r\n");
+
+        assert_eq!(encoded.expect("Encoding should succeed"), expected);
+    }
+
+    #[tokio::test]
+    async fn test_encode_reports_missing_files() {
+        let multipart = Multipart::parse(BODY, "WebKitBoundary").expect("Expected parts");
+        let error = multipart.encode().await.unwrap_err();
+
+        assert!(error.to_string().contains("Failed to read multipart file"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{document, parser, response_variables, variables};
+use tower_lsp::lsp_types::{Position, Range};
+
+/// A name that can be navigated to, referenced and renamed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    /// A file or environment variable, used as `{{name}}`
+    Variable(String),
+    /// A `# @name` request, used as `{{name.response...}}`
+    Request(String),
+}
+
+/// Where a symbol's name appears on a line
+#[derive(Debug)]
+struct Occurrence {
+    symbol: Symbol,
+    line: usize,
+    /// Byte range of the name within the line
+    start: usize,
+    end: usize,
+    is_definition: bool,
+}
+
+impl Occurrence {
+    fn new(symbol: Symbol, line_idx: usize, line: &str, name: &str, is_definition: bool) -> Self {
+        let start = name.as_ptr() as usize - line.as_ptr() as usize;
+        Occurrence {
+            symbol,
+            line: line_idx,
+            start,
+            end: start + name.len(),
+            is_definition,
+        }
+    }
+
+    fn range(&self, line: &str) -> Range {
+        let column = |offset: usize| parser::utf16_len(&line[..offset]) as u32;
+        Range::new(
+            Position::new(self.line as u32, column(self.start)),
+            Position::new(self.line as u32, column(self.end)),
+        )
+    }
+}
+
+/// Every declaration and use of a variable or named request in the document
+fn occurrences(text: &str) -> Vec<(Occurrence, &str)> {
+    let mut found = Vec::new();
+    let mut request_seen = false;
+
+    for (idx, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("###") {
+            request_seen = false;
+        } else if !request_seen && parser::is_request_line(trimmed) {
+            request_seen = true;
+        }
+
+        if request_seen {
+            // Past the request line, only placeholders count
+        } else if let Some(name) = parser::parse_name_comment(trimmed) {
+            let symbol = Symbol::Request(name.to_string());
+            found.push((Occurrence::new(symbol, idx, line, name, true), line));
+        } else if let Some((name, _)) = variables::parse_variable_declaration(trimmed) {
+            let after_at = &line[line.find('@').map_or(0, |at| at + 1)..];
+            if let Some(offset) = after_at.find(&name) {
+                let name = &after_at[offset..offset + name.len()];
+                let symbol = Symbol::Variable(name.to_string());
+                found.push((Occurrence::new(symbol, idx, line, name, true), line));
+            }
+        }
+
+        for placeholder in variables::placeholder_spans(line) {
+            let expression = placeholder.expression;
+            let (symbol, name) = match response_variables::parse_reference(expression) {
+                Some(reference) => (
+                    Symbol::Request(reference.request_name.to_string()),
+                    &expression[..reference.request_name.len()],
+                ),
+                None if variables::is_variable_name(expression) => {
+                    (Symbol::Variable(expression.to_string()), expression)
+                }
+                None => continue,
+            };
+            found.push((Occurrence::new(symbol, idx, line, name, false), line));
+        }
+    }
+
+    found
+}
+
+/// The variable or request name under the cursor
+pub fn symbol_at(text: &str, position: Position) -> Option<Symbol> {
+    let line = text.lines().nth(position.line as usize)?;
+    let cursor = document::offset_at(line, Position::new(0, position.character));
+
+    occurrences(text)
+        .into_iter()
+        .map(|(occurrence, _)| occurrence)
+        .find(|occurrence| {
+            occurrence.line == position.line as usize
+                && occurrence.start <= cursor
+                && cursor <= occurrence.end
+        })
+        .map(|occurrence| occurrence.symbol)
+}
+
+/// The declaration of `symbol` used on `line`. A variable declared more than once resolves to
+/// the last declaration above the line, as it does when the file is parsed, or to the first
+/// one below it when it is used before being declared. Requests resolve to their last
+/// declaration.
+pub fn definition(text: &str, symbol: &Symbol, line: usize) -> Option<Range> {
+    let occurrences = occurrences(text);
+    let mut declarations = occurrences
+        .iter()
+        .filter(|(occurrence, _)| occurrence.is_definition && occurrence.symbol == *symbol);
+
+    let found = match symbol {
+        Symbol::Variable(_) => declarations
+            .clone()
+            .rev()
+            .find(|(occurrence, _)| occurrence.line <= line)
+            .or_else(|| declarations.next()),
+        Symbol::Request(_) => declarations.next_back(),
+    };
+    found.map(|(occurrence, line)| occurrence.range(line))
+}
+
+/// Ranges of every use of `symbol`'s name, optionally including its declarations
+pub fn references(text: &str, symbol: &Symbol, include_declaration: bool) -> Vec<Range> {
+    occurrences(text)
+        .iter()
+        .filter(|(occurrence, _)| {
+            occurrence.symbol == *symbol && (include_declaration || !occurrence.is_definition)
+        })
+        .map(|(occurrence, line)| occurrence.range(line))
+        .collect()
+}
+
+/// Every environment defining `name` in an environment file, with the range of its key
+pub fn environment_keys<'a>(content: &'a str, name: &str) -> Vec<(&'a str, Range)> {
+    let mut keys = Vec::new();
+    let mut environment = "";
+
+    for (depth, key, start) in json_keys(content) {
+        if depth == 1 {
+            environment = key;
+        } else if depth == 2 && key == name {
+            keys.push((
+                environment,
+                Range::new(
+                    document::position_at(content, start),
+                    document::position_at(content, start + key.len()),
+                ),
+            ));
+        }
+    }
+
+    keys
+}
+
+/// Object keys of a JSON document with their nesting depth and the byte offset where the
+/// key starts, after its opening quote. Keys containing escapes are returned raw.
+fn json_keys(content: &str) -> Vec<(usize, &str, usize)> {
+    let mut keys = Vec::new();
+    let mut depth: usize = 0;
+    let mut chars = content.char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth = depth.saturating_sub(1),
+            '"' => {
+                let start = idx + 1;
+                let mut end = content.len();
+                let mut escaped = false;
+                for (idx, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            end = idx;
+                            break;
+                        }
+                        _ => escaped = false,
+                    }
+                }
+
+                if content[end..].chars().skip(1).find(|c| !c.is_whitespace()) == Some(':') {
+                    keys.push((depth, &content[start..end], start));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "@host = example.com
+@url = https://{{host}}
+# @name login
+POST {{url}}/login
+
+###
+GET {{ url }}/me?host={{host}}
+Authorization: Bearer {{login.response.body.$.token}}
+X-Env: {{token}}";
+
+    const ENV: &str = r#"{
+  "$shared": { "token": "shared" },
+  "dev": {
+    "url": "http://localhost",
+    "token": "dev-token",
+    "nested": { "token": "not a variable" }
+  }
+}"#;
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    fn variable(name: &str) -> Symbol {
+        Symbol::Variable(name.to_string())
+    }
+
+    #[rstest]
+    #[case(0, 2, Some(variable("host")))]
+    #[case(1, 18, Some(variable("host")))]
+    #[case(2, 9, Some(Symbol::Request("login".to_string())))]
+    #[case(6, 8, Some(variable("url")))]
+    #[case(7, 26, Some(Symbol::Request("login".to_string())))]
+    #[case(3, 1, None)]
+    fn test_symbol_at(#[case] line: u32, #[case] character: u32, #[case] expected: Option<Symbol>) {
+        assert_eq!(
+            symbol_at(DOCUMENT, Position::new(line, character)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_definition() {
+        assert_eq!(
+            definition(DOCUMENT, &variable("url"), 6),
+            Some(range(1, 1, 4))
+        );
+        assert_eq!(
+            definition(DOCUMENT, &Symbol::Request("login".to_string()), 7),
+            Some(range(2, 8, 13))
+        );
+        assert_eq!(definition(DOCUMENT, &variable("token"), 8), None);
+    }
+
+    #[rstest]
+    #[case(0, range(2, 1, 5))]
+    #[case(3, range(2, 1, 5))]
+    #[case(5, range(5, 1, 5))]
+    #[case(6, range(5, 1, 5))]
+    fn test_definition_of_redeclared_variable(#[case] line: usize, #[case] expected: Range) {
+        let text = "GET https://{{host}}/\n###\n@host = a.com\nGET https://{{host}}/\n###\n@host = b.com\nGET https://{{host}}/";
+
+        assert_eq!(definition(text, &variable("host"), line), Some(expected));
+    }
+
+    #[rstest]
+    #[case(variable("host"), true, vec![range(0, 1, 5), range(1, 17, 21), range(6, 24, 28)])]
+    #[case(variable("host"), false, vec![range(1, 17, 21), range(6, 24, 28)])]
+    #[case(variable("url"), false, vec![range(3, 7, 10), range(6, 7, 10)])]
+    #[case(
+        Symbol::Request("login".to_string()),
+        true,
+        vec![range(2, 8, 13), range(7, 24, 29)]
+    )]
+    fn test_references(
+        #[case] symbol: Symbol,
+        #[case] include_declaration: bool,
+        #[case] expected: Vec<Range>,
+    ) {
+        assert_eq!(references(DOCUMENT, &symbol, include_declaration), expected);
+    }
+
+    #[test]
+    fn test_environment_keys_only_match_variables() {
+        let keys = environment_keys(ENV, "token");
+
+        assert_eq!(
+            keys,
+            vec![("$shared", range(1, 16, 21)), ("dev", range(4, 5, 10))]
+        );
+        assert_eq!(environment_keys(ENV, "url"), vec![("dev", range(3, 5, 8))]);
+        assert!(environment_keys(ENV, "missing").is_empty());
+    }
+
+    #[rstest]
+    #[case(r#"} {"dev": {"url": 1}}"#, vec![(1, "dev"), (2, "url")])]
+    #[case(r#"]] {"dev": {"url": 1}}"#, vec![(1, "dev"), (2, "url")])]
+    #[case(r#"{"dev": {"url": 1}}} "a": 2"#, vec![(1, "dev"), (2, "url"), (0, "a")])]
+    #[case(r#"{"dev": {"url": 1]]] } {"b": 2"#, vec![(1, "dev"), (2, "url"), (1, "b")])]
+    fn test_json_keys_tolerates_unbalanced_brackets(
+        #[case] content: &str,
+        #[case] expected: Vec<(usize, &str)>,
+    ) {
+        let keys: Vec<(usize, &str)> = json_keys(content)
+            .into_iter()
+            .map(|(depth, key, _)| (depth, key))
+            .collect();
+
+        assert_eq!(keys, expected);
+    }
+}
@@ -1,17 +1,156 @@
+use crate::headers::Headers;
+use crate::multipart::{self, Multipart};
+use crate::variables;
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
 use url::Url;
 
+/// Sends the request without the cookie jar's cookies and keeps none it receives
+pub const NO_COOKIE_JAR: &str = "no-cookie-jar";
+/// Keeps the response out of http-responses.http
+pub const NO_LOG: &str = "no-log";
+/// Returns redirect responses instead of following them
+pub const NO_REDIRECT: &str = "no-redirect";
+/// Accepts invalid TLS certificates for this request
+pub const INSECURE: &str = "insecure";
+/// Overrides the default timeout, e.g. `# @timeout 5s`
+pub const TIMEOUT: &str = "timeout";
+/// Limits how many redirects are followed, e.g. `# @max-redirects 3`
+pub const MAX_REDIRECTS: &str = "max-redirects";
+
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
     pub method: String,
     pub url: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub body: Option<String>,
+    /// Body read from a file with `< ./path` or `<@ ./path`, used instead of `body`
+    pub body_file: Option<BodyFile>,
+    /// Parts of a `multipart/form-data` body, sent instead of the raw `body` text
+    pub multipart: Option<Multipart>,
     pub line_number: usize,
+    /// Name given with `# @name`, used to reference this request's response
+    pub name: Option<String>,
+    /// Text after the `###` separator that starts the request's block
+    pub title: Option<String>,
+    /// Lines of the request's block, from its `###` separator (or first non-blank line)
+    /// through its last non-blank line
+    pub block_lines: Range<usize>,
+    /// Lines holding the body, without surrounding blank lines
+    pub body_lines: Option<Range<usize>>,
+    /// Values of the block's `# @directive value` comments, by directive name; flags such as
+    /// `# @no-log` have no value. `# @name` is kept in `name` instead.
+    pub directives: HashMap<String, Option<String>>,
+}
+
+impl HttpRequest {
+    /// Whether the block has the given `# @<directive>` comment, with or without a value
+    pub fn has_directive(&self, name: &str) -> bool {
+        self.directives.contains_key(name)
+    }
+
+    /// Timeout set with `# @timeout`, overriding the client's default
+    pub fn timeout(&self) -> Result<Option<Duration>, String> {
+        self.directives
+            .get(TIMEOUT)
+            .map(|value| parse_timeout(value.as_deref()))
+            .transpose()
+    }
+
+    /// How many redirects to follow: none with `# @no-redirect`, at most the
+    /// `# @max-redirects` limit, or the client's default when neither is given
+    pub fn max_redirects(&self) -> Result<Option<usize>, String> {
+        if self.has_directive(NO_REDIRECT) {
+            return Ok(Some(0));
+        }
+
+        self.directives
+            .get(MAX_REDIRECTS)
+            .map(|value| parse_max_redirects(value.as_deref()))
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyFile {
+    /// Path as written; relative paths are resolved against the .http file's directory
+    pub path: PathBuf,
+    /// Whether `{{variables}}` in the file's contents are substituted (`<@`)
+    pub substitute_variables: bool,
+}
+
+/// A problem found while parsing, located on a single line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    /// Columns are counted in UTF-16 code units, as LSP positions are
+    pub start_column: usize,
+    pub end_column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    /// Creates an error spanning `part`, which must be a subslice of `line`
+    fn spanning(line_idx: usize, line: &str, part: &str, message: String) -> Self {
+        let start = part.as_ptr() as usize - line.as_ptr() as usize;
+        let start_column = utf16_len(&line[..start]);
+        ParseError {
+            line: line_idx,
+            start_column,
+            end_column: start_column + utf16_len(part),
+            message,
+        }
+    }
+}
+
+/// Length of `text` in UTF-16 code units, the unit of LSP columns
+pub fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+/// Requests parsed from a document along with the errors found in it
+#[derive(Debug, Clone, Default)]
+pub struct ParsedDocument {
+    pub requests: Vec<HttpRequest>,
+    pub errors: Vec<ParseError>,
+    /// File-level `@name = value` declarations in document order
+    pub declarations: Vec<Declaration>,
 }
 
+/// A file-level `@name = value` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    pub name: String,
+    /// The value with variables declared above it already substituted
+    pub value: String,
+    pub line: usize,
+}
+
+impl ParsedDocument {
+    /// The declaration that gives `name` its value on `line`, the last one above it
+    pub fn declaration(&self, name: &str, line: usize) -> Option<&Declaration> {
+        self.declarations
+            .iter()
+            .rev()
+            .find(|declaration| declaration.line < line && declaration.name == name)
+    }
+
+    /// File variables in effect on `line`, from the declarations above it
+    pub fn variables_at(&self, line: usize) -> HashMap<String, String> {
+        self.declarations
+            .iter()
+            .take_while(|declaration| declaration.line < line)
+            .map(|declaration| (declaration.name.clone(), declaration.value.clone()))
+            .collect()
+    }
+}
+
+pub const VALID_METHODS: [&str; 7] = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS"];
+
 /// Validates that a URL is safe and well-formed for HTTP requests
-fn validate_url(url_str: &str) -> Result<String, String> {
+pub fn validate_url(url_str: &str) -> Result<String, String> {
     // Check URL length to prevent abuse
     if url_str.len() > 2048 {
         return Err(format!(
@@ -41,9 +180,16 @@ fn validate_url(url_str: &str) -> Result<String, String> {
 }
 
 pub fn parse_http_file(content: &str) -> Vec<HttpRequest> {
-    let mut requests = Vec::new();
+    parse_document(content).requests
+}
+
+/// Parses every request in a document, reporting malformed blocks as errors
+pub fn parse_document(content: &str) -> ParsedDocument {
     let lines: Vec<&str> = content.lines().collect();
-    let _valid_methods = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS"];
+    let mut document = ParsedDocument {
+        declarations: collect_file_variables(&lines),
+        ..Default::default()
+    };
 
     let mut current_block_start: Option<usize> = Some(0);
 
@@ -54,8 +200,11 @@ pub fn parse_http_file(content: &str) -> Vec<HttpRequest> {
         if trimmed.starts_with("###") {
             // Parse the current block up to this delimiter
             if let Some(start) = current_block_start {
-                if let Some(request) = parse_block_lines(&lines, start, line_idx) {
-                    requests.push(request);
+                let variables = document.variables_at(line_idx);
+                if let Some(request) =
+                    parse_block_lines(&lines, start, line_idx, &variables, &mut document.errors)
+                {
+                    document.requests.push(request);
                 }
             }
             // Start new block after this delimiter
@@ -65,46 +214,266 @@ pub fn parse_http_file(content: &str) -> Vec<HttpRequest> {
 
     // Don't forget the last block
     if let Some(start) = current_block_start {
-        if let Some(request) = parse_block_lines(&lines, start, lines.len()) {
-            requests.push(request);
+        let variables = document.variables_at(lines.len());
+        if let Some(request) =
+            parse_block_lines(&lines, start, lines.len(), &variables, &mut document.errors)
+        {
+            document.requests.push(request);
+        }
+    }
+
+    document
+}
+
+/// Collects `@name = value` declarations that appear before the request line of a block,
+/// in document order. Each value may reference variables declared above it.
+fn collect_file_variables(lines: &[&str]) -> Vec<Declaration> {
+    let mut variables = HashMap::new();
+    let mut declarations = Vec::new();
+    let mut in_request = false;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("###") {
+            in_request = false;
+        } else if !in_request {
+            if let Some((name, value)) = variables::parse_variable_declaration(trimmed) {
+                let value = variables::substitute(&value, &variables);
+                variables.insert(name.clone(), value.clone());
+                declarations.push(Declaration {
+                    name,
+                    value,
+                    line: idx,
+                });
+            } else if is_request_line(trimmed) {
+                in_request = true;
+            }
         }
     }
 
-    requests
+    declarations
+}
+
+/// Whether a trimmed line starts a request, e.g. `GET https://example.com`
+pub fn is_request_line(trimmed: &str) -> bool {
+    let mut parts = split_request_line(trimmed).into_iter();
+    match (parts.next(), parts.next()) {
+        (Some(method), Some(_)) => VALID_METHODS.contains(&method.to_uppercase().as_str()),
+        _ => false,
+    }
+}
+
+/// Parses a body consisting of a single `< ./path` or `<@ ./path` line
+pub fn parse_body_file_reference(body: &str) -> Option<BodyFile> {
+    if body.contains('\n') {
+        return None;
+    }
+
+    let (rest, substitute_variables) = match body.strip_prefix("<@") {
+        Some(rest) => (rest, true),
+        None => (body.strip_prefix('<')?, false),
+    };
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let path = rest.trim();
+    (!path.is_empty()).then(|| BodyFile {
+        path: PathBuf::from(path),
+        substitute_variables,
+    })
+}
+
+/// Extracts the name from a `# @name login` or `// @name login` comment
+pub fn parse_name_comment(trimmed: &str) -> Option<&str> {
+    let comment = trimmed
+        .strip_prefix('#')
+        .or_else(|| trimmed.strip_prefix("//"))?;
+    let name = comment.trim_start().strip_prefix("@name")?;
+    if !name.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let name = name.trim();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Splits a `# @directive value` comment into the directive's name and its value, if any
+pub fn parse_directive(trimmed: &str) -> Option<(&str, Option<&str>)> {
+    let comment = trimmed
+        .strip_prefix('#')
+        .or_else(|| trimmed.strip_prefix("//"))?;
+    let directive = comment.trim_start().strip_prefix('@')?;
+    let (name, value) = match directive.split_once(char::is_whitespace) {
+        Some((name, value)) => (name, Some(value.trim()).filter(|value| !value.is_empty())),
+        None => (directive, None),
+    };
+
+    (!name.is_empty()).then_some((name, value))
 }
 
-fn parse_block_lines(lines: &[&str], start_idx: usize, end_idx: usize) -> Option<HttpRequest> {
-    let valid_methods = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS"];
+/// Parses a `# @timeout` value: milliseconds, or a number followed by `ms`, `s` or `m`
+fn parse_timeout(value: Option<&str>) -> Result<Duration, String> {
+    let invalid = || "@timeout needs a duration such as 500, 500ms, 5s or 1m".to_string();
+    let value = value.ok_or_else(invalid)?;
+    let (number, unit_ms) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 1)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1000)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60_000)
+    } else {
+        (value, 1)
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|number| *number > 0)
+        .and_then(|number| number.checked_mul(unit_ms))
+        .map(Duration::from_millis)
+        .ok_or_else(invalid)
+}
+
+fn parse_max_redirects(value: Option<&str>) -> Result<usize, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| "@max-redirects needs a number of redirects".to_string())
+}
+
+/// Checks the value of a directive the executor reads. Values holding `{{variables}}` are
+/// checked once they are resolved.
+fn check_directive(name: &str, value: Option<&str>) -> Result<(), String> {
+    if value.is_some_and(|value| value.contains("{{")) {
+        return Ok(());
+    }
 
+    match name {
+        TIMEOUT => parse_timeout(value).map(drop),
+        MAX_REDIRECTS => parse_max_redirects(value).map(drop),
+        _ => Ok(()),
+    }
+}
+
+/// Extracts the title from a `### Create user` separator line
+pub fn parse_separator_title(line: &str) -> Option<String> {
+    let title = line.trim().trim_start_matches('#').trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Splits a request line on whitespace, keeping `{{...}}` placeholders such as
+/// `{{$randomInt 1 10}}` together as part of their token
+pub fn split_request_line(trimmed: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut token_start = None;
+    let mut in_placeholder = false;
+    let mut chars = trimmed.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        if c.is_whitespace() && !in_placeholder {
+            if let Some(start) = token_start.take() {
+                parts.push(&trimmed[start..idx]);
+            }
+            continue;
+        }
+
+        token_start.get_or_insert(idx);
+        if c == '{' && next == Some('{') {
+            in_placeholder = true;
+            chars.next();
+        } else if c == '}' && next == Some('}') && in_placeholder {
+            in_placeholder = false;
+            chars.next();
+        }
+    }
+
+    if let Some(start) = token_start {
+        parts.push(&trimmed[start..]);
+    }
+
+    parts
+}
+
+fn parse_block_lines(
+    lines: &[&str],
+    start_idx: usize,
+    end_idx: usize,
+    variables: &HashMap<String, String>,
+    errors: &mut Vec<ParseError>,
+) -> Option<HttpRequest> {
     let mut method = String::new();
     let mut url = String::new();
-    let mut headers = HashMap::new();
+    let mut headers = Headers::new();
     let mut body_lines = Vec::new();
     let mut request_line_number: Option<usize> = None;
     let mut in_body = false;
+    let mut name = None;
+    let mut directives = HashMap::new();
+    let title = start_idx
+        .checked_sub(1)
+        .and_then(|separator| parse_separator_title(lines[separator]));
+
+    let mut first_line = start_idx.checked_sub(1);
+    let mut last_line = start_idx;
+    let mut body_range: Option<Range<usize>> = None;
 
     for (offset, line) in lines[start_idx..end_idx].iter().enumerate() {
         let idx = start_idx + offset;
         let trimmed = line.trim();
 
+        if !trimmed.is_empty() {
+            first_line.get_or_insert(idx);
+            last_line = idx;
+        }
+
         // Skip empty lines before finding the request
         if request_line_number.is_none() && trimmed.is_empty() {
             continue;
         }
 
-        // Skip comments
-        if trimmed.starts_with('#') || trimmed.starts_with("//") {
+        // Skip comments outside the body. Only those before the request line can name the
+        // request or hold directives.
+        if !in_body && (trimmed.starts_with('#') || trimmed.starts_with("//")) {
+            if request_line_number.is_some() {
+                continue;
+            }
+            if let Some(request_name) = parse_name_comment(trimmed) {
+                name = Some(request_name.to_string());
+            } else if let Some((directive, value)) = parse_directive(trimmed) {
+                let value = value.map(|value| variables::substitute(value, variables));
+                if let Err(message) = check_directive(directive, value.as_deref()) {
+                    errors.push(ParseError::spanning(idx, line, trimmed, message));
+                }
+                directives.insert(directive.to_string(), value);
+            }
+            continue;
+        }
+
+        // Skip variable declarations, which are collected up front
+        if request_line_number.is_none() && variables::parse_variable_declaration(trimmed).is_some()
+        {
             continue;
         }
 
         // Try to find HTTP request line
         if request_line_number.is_none() {
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
+            let parts = split_request_line(trimmed);
             if parts.len() >= 2 {
                 let potential_method = parts[0].to_uppercase();
-                if valid_methods.contains(&potential_method.as_str()) {
-                    // Validate the URL before accepting it
-                    match validate_url(parts[1]) {
+                if VALID_METHODS.contains(&potential_method.as_str()) {
+                    // Expand variables and validate the URL before accepting it.
+                    // URLs that still reference environment variables are validated
+                    // once they are resolved at send time.
+                    let expanded_url = variables::substitute(parts[1], variables);
+                    let validated = if variables::has_placeholders(&expanded_url) {
+                        Ok(expanded_url)
+                    } else {
+                        validate_url(&expanded_url)
+                    };
+                    match validated {
                         Ok(validated_url) => {
                             method = potential_method;
                             url = validated_url;
@@ -112,40 +481,85 @@ fn parse_block_lines(lines: &[&str], start_idx: usize, end_idx: usize) -> Option
                             continue;
                         }
                         Err(err) => {
-                            // Skip this invalid request and log the error
-                            eprintln!("Skipping invalid URL at line {}: {}", idx + 1, err);
-                            continue;
+                            // Skip the whole block rather than guessing at its request line
+                            errors.push(ParseError::spanning(idx, line, parts[1], err));
+                            return None;
                         }
                     }
+                } else if parts[0].chars().all(|c| c.is_ascii_alphabetic()) {
+                    errors.push(ParseError::spanning(
+                        idx,
+                        line,
+                        parts[0],
+                        format!(
+                            "Unknown HTTP method '{}'. Expected one of: {}",
+                            parts[0],
+                            VALID_METHODS.join(", ")
+                        ),
+                    ));
                 }
             }
         } else if in_body {
             // Collect body lines
             body_lines.push(*line);
+            if !trimmed.is_empty() {
+                match &mut body_range {
+                    Some(range) => range.end = idx + 1,
+                    None => body_range = Some(idx..idx + 1),
+                }
+            }
         } else if trimmed.is_empty() {
             // Empty line marks start of body
             in_body = true;
         } else if let Some(colon_idx) = trimmed.find(':') {
             // Parse header
-            let name = trimmed[..colon_idx].trim().to_string();
-            let value = trimmed[colon_idx + 1..].trim().to_string();
-            headers.insert(name, value);
+            let name = variables::substitute(trimmed[..colon_idx].trim(), variables);
+            let value = variables::substitute(trimmed[colon_idx + 1..].trim(), variables);
+            headers.append(name, value);
+        } else {
+            errors.push(ParseError::spanning(
+                idx,
+                line,
+                trimmed,
+                format!("Invalid header line '{}'. Expected 'Name: value'", trimmed),
+            ));
         }
     }
 
     request_line_number.map(|line_num| {
-        let body = if body_lines.is_empty() {
+        let mut body = if body_lines.is_empty() {
             None
         } else {
-            Some(body_lines.join("\n").trim().to_string())
+            Some(variables::substitute(
+                body_lines.join("\n").trim(),
+                variables,
+            ))
         };
 
+        let body_file = body.as_deref().and_then(parse_body_file_reference);
+        if body_file.is_some() {
+            body = None;
+        }
+
+        let multipart = headers
+            .get("content-type")
+            .and_then(|content_type| multipart::boundary(content_type))
+            .zip(body.as_deref())
+            .and_then(|(boundary, body)| Multipart::parse(body, boundary));
+
         HttpRequest {
             method,
             url,
             headers,
             body,
+            body_file,
+            multipart,
             line_number: line_num,
+            name,
+            title,
+            block_lines: first_line.unwrap_or(line_num)..last_line + 1,
+            body_lines: body_range,
+            directives,
         }
     })
 }
@@ -556,4 +970,542 @@ Content-Type: application/json
         // Should skip the malicious request
         assert_eq!(requests.len(), 0);
     }
+
+    // File variable tests
+    #[test]
+    fn test_parse_substitutes_file_variables() {
+        let content = r#"@baseUrl = https://api.example.com
+@token = abc123
+
+POST {{baseUrl}}/users
+Authorization: Bearer {{token}}
+Content-Type: application/json
+
+{"token": "{{token}}"}"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "https://api.example.com/users");
+        assert_eq!(
+            requests[0].headers.get("Authorization"),
+            Some(&"Bearer abc123".to_string())
+        );
+        assert_eq!(requests[0].body.as_deref(), Some(r#"{"token": "abc123"}"#));
+        assert_eq!(requests[0].line_number, 3);
+    }
+
+    #[test]
+    fn test_parse_file_variables_apply_to_every_block() {
+        let content = r#"@host = example.com
+
+###
+GET https://{{host}}/one
+###
+@version = v2
+GET https://{{host}}/{{version}}/two"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].url, "https://example.com/one");
+        assert_eq!(requests[1].url, "https://example.com/v2/two");
+    }
+
+    #[test]
+    fn test_parse_file_variables_can_reference_earlier_variables() {
+        let content = r#"@host = example.com
+@baseUrl = https://{{host}}/api
+GET {{baseUrl}}/users"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "https://example.com/api/users");
+    }
+
+    #[test]
+    fn test_parse_redeclared_variables_apply_from_their_declaration() {
+        let content = r#"@token = first
+GET https://example.com/one?token={{token}}
+###
+GET https://example.com/two?token={{token}}&id={{id}}
+###
+@token = second-{{token}}
+@id = 3
+GET https://example.com/three?token={{token}}&id={{id}}"#;
+
+        let urls: Vec<String> = parse_document(content)
+            .requests
+            .into_iter()
+            .map(|request| request.url)
+            .collect();
+
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/one?token=first",
+                "https://example.com/two?token=first&id={{id}}",
+                "https://example.com/three?token=second-first&id=3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_declarations_are_not_headers() {
+        let content = r#"@baseUrl = https://example.com
+GET {{baseUrl}}/api
+Accept: application/json"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].headers.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_body_declaration_like_lines_are_kept() {
+        let content = r#"POST https://example.com/api
+
+@notAVariable = value"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].body.as_deref(), Some("@notAVariable = value"));
+    }
+
+    #[test]
+    fn test_parse_defers_validation_of_unresolved_urls() {
+        let content = "GET {{baseUrl}}/users";
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "{{baseUrl}}/users");
+    }
+
+    #[rstest]
+    #[case("GET https://example.com HTTP/1.1", vec!["GET", "https://example.com", "HTTP/1.1"])]
+    #[case(
+        "GET https://example.com/{{$randomInt 1 10}}",
+        vec!["GET", "https://example.com/{{$randomInt 1 10}}"]
+    )]
+    #[case(
+        "POST  {{ baseUrl }}/users  HTTP/2",
+        vec!["POST", "{{ baseUrl }}/users", "HTTP/2"]
+    )]
+    #[case("GET", vec!["GET"])]
+    fn test_split_request_line(#[case] line: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(split_request_line(line), expected);
+    }
+
+    #[test]
+    fn test_parse_keeps_system_variables_for_send_time() {
+        let content = r#"POST https://example.com/items/{{$randomInt 1 100}}
+X-Request-Id: {{$uuid}}
+
+{"createdAt": "{{$isoTimestamp}}"}"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].url,
+            "https://example.com/items/{{$randomInt 1 100}}"
+        );
+        assert_eq!(
+            requests[0].headers.get("X-Request-Id"),
+            Some(&"{{$uuid}}".to_string())
+        );
+        assert_eq!(
+            requests[0].body.as_deref(),
+            Some(r#"{"createdAt": "{{$isoTimestamp}}"}"#)
+        );
+    }
+    // Named request tests
+    #[rstest]
+    #[case("# @name login", Some("login"))]
+    #[case("// @name create-user", Some("create-user"))]
+    #[case("#   @name   spaced  ", Some("spaced"))]
+    #[case("# @name", None)]
+    #[case("# @names login", None)]
+    #[case("# just a comment", None)]
+    fn test_parse_name_comment(#[case] line: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_name_comment(line), expected);
+    }
+
+    #[test]
+    fn test_parse_named_requests() {
+        let content = r#"# @name login
+POST https://example.com/login
+
+{"user": "jane"}
+
+###
+GET https://example.com/profile
+Authorization: Bearer {{login.response.body.$.token}}"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].name.as_deref(), Some("login"));
+        assert_eq!(requests[1].name, None);
+        assert_eq!(
+            requests[1].headers.get("Authorization"),
+            Some(&"Bearer {{login.response.body.$.token}}".to_string())
+        );
+    }
+
+    #[rstest]
+    #[case("# @no-cookie-jar", Some(("no-cookie-jar", None)))]
+    #[case("// @timeout 5 s ", Some(("timeout", Some("5 s"))))]
+    #[case("#@insecure", Some(("insecure", None)))]
+    #[case("# @", None)]
+    #[case("### @name", None)]
+    #[case("# plain comment", None)]
+    fn test_parse_directive(#[case] line: &str, #[case] expected: Option<(&str, Option<&str>)>) {
+        assert_eq!(parse_directive(line), expected);
+    }
+
+    #[test]
+    fn test_parse_collects_directives() {
+        let content = "@wait = 2s\n\n# @no-cookie-jar\n// @timeout {{wait}}\n# @name login\n\
+                       GET https://example.com/a\n\n###\nGET https://example.com/b";
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(
+            requests[0].directives,
+            HashMap::from([
+                (NO_COOKIE_JAR.to_string(), None),
+                (TIMEOUT.to_string(), Some("2s".to_string())),
+            ])
+        );
+        assert_eq!(requests[0].name.as_deref(), Some("login"));
+        assert!(requests[0].has_directive(NO_COOKIE_JAR));
+        assert!(requests[1].directives.is_empty());
+    }
+
+    #[rstest]
+    #[case("", Ok(None), Ok(None))]
+    #[case("# @timeout 250\n", Ok(Some(Duration::from_millis(250))), Ok(None))]
+    #[case(
+        "# @timeout 250ms\n# @max-redirects 3\n",
+        Ok(Some(Duration::from_millis(250))),
+        Ok(Some(3))
+    )]
+    #[case(
+        "# @timeout 5s\n# @no-redirect\n# @max-redirects 3\n",
+        Ok(Some(Duration::from_secs(5))),
+        Ok(Some(0))
+    )]
+    #[case("# @timeout 2m\n", Ok(Some(Duration::from_secs(120))), Ok(None))]
+    #[case("# @timeout\n# @max-redirects many\n", Err(()), Err(()))]
+    #[case("# @timeout 0\n", Err(()), Ok(None))]
+    #[case("# @timeout 1h\n", Err(()), Ok(None))]
+    fn test_directive_values(
+        #[case] directives: &str,
+        #[case] timeout: std::result::Result<Option<Duration>, ()>,
+        #[case] max_redirects: std::result::Result<Option<usize>, ()>,
+    ) {
+        let document = parse_document(&format!("{}GET https://example.com", directives));
+        let request = &document.requests[0];
+
+        assert_eq!(request.timeout().map_err(drop), timeout);
+        assert_eq!(request.max_redirects().map_err(drop), max_redirects);
+        assert_eq!(
+            document.errors.is_empty(),
+            timeout.is_ok() && max_redirects.is_ok()
+        );
+    }
+
+    #[test]
+    fn test_parse_keeps_comments_in_body() {
+        let content = "POST https://x.com\nContent-Type: text/plain\n\n# @name evil\nline";
+        let document = parse_document(content);
+        let request = &document.requests[0];
+
+        assert_eq!(request.name, None);
+        assert!(request.directives.is_empty());
+        assert_eq!(request.body.as_deref(), Some("# @name evil\nline"));
+    }
+
+    #[test]
+    fn test_parse_ignores_directives_after_request_line() {
+        let content = "GET https://x.com\n# @no-redirect\nAccept: */*";
+        let request = &parse_document(content).requests[0];
+
+        assert!(!request.has_directive("no-redirect"));
+        assert_eq!(request.headers.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_reports_invalid_directive_values() {
+        let document = parse_document("# @timeout soon\nGET https://example.com");
+
+        assert_eq!(
+            document.errors,
+            vec![ParseError {
+                line: 0,
+                start_column: 0,
+                end_column: 15,
+                message: "@timeout needs a duration such as 500, 500ms, 5s or 1m".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_keeps_duplicate_headers_in_order() {
+        let content = r#"GET https://example.com/api
+Accept: application/json
+Cookie: a=1
+Accept: text/plain
+Cookie: b=2"#;
+
+        let requests = parse_http_file(content);
+        let headers: Vec<(&str, &str)> = requests[0]
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+
+        assert_eq!(
+            headers,
+            vec![
+                ("Accept", "application/json"),
+                ("Cookie", "a=1"),
+                ("Accept", "text/plain"),
+                ("Cookie", "b=2"),
+            ]
+        );
+    }
+    // Body file tests
+    #[rstest]
+    #[case("< ./fixtures/user.json", Some(("./fixtures/user.json", false)))]
+    #[case("<@ ./fixtures/user.json", Some(("./fixtures/user.json", true)))]
+    #[case("<   /abs/path.bin  ", Some(("/abs/path.bin", false)))]
+    #[case("<user>jane</user>", None)]
+    #[case("<?xml version=\"1.0\"?>", None)]
+    #[case("<@./missing-space.json", None)]
+    #[case("< ./a.json\n< ./b.json", None)]
+    #[case("<", None)]
+    fn test_parse_body_file_reference(#[case] body: &str, #[case] expected: Option<(&str, bool)>) {
+        let expected = expected.map(|(path, substitute_variables)| BodyFile {
+            path: PathBuf::from(path),
+            substitute_variables,
+        });
+        assert_eq!(parse_body_file_reference(body), expected);
+    }
+
+    #[test]
+    fn test_parse_request_with_body_file() {
+        let content = r#"@fixtures = ./fixtures
+POST https://example.com/users
+Content-Type: application/json
+
+<@ {{fixtures}}/user.json"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].body, None);
+        assert_eq!(
+            requests[0].body_file,
+            Some(BodyFile {
+                path: PathBuf::from("./fixtures/user.json"),
+                substitute_variables: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_xml_body_is_not_a_body_file() {
+        let content = r#"POST https://example.com/users
+Content-Type: application/xml
+
+<user>jane</user>"#;
+
+        let requests = parse_http_file(content);
+
+        assert_eq!(requests[0].body.as_deref(), Some("<user>jane</user>"));
+        assert_eq!(requests[0].body_file, None);
+    }
+
+    #[test]
+    fn test_parse_multipart_body() {
+        let content = r#"POST https://example.com/upload
+Content-Type: multipart/form-data; boundary=----Boundary
+
+------Boundary
+Content-Disposition: form-data; name="description"
+
+Quarterly report
+------Boundary
+Content-Disposition: form-data; name="file"; filename="report.pdf"
+Content-Type: application/pdf
+
+< ./report.pdf
+------Boundary--"#;
+
+        let requests = parse_http_file(content);
+        let multipart = requests[0]
+            .multipart
+            .as_ref()
+            .expect("Expected a multipart body");
+
+        assert_eq!(multipart.boundary, "----Boundary");
+        assert_eq!(multipart.parts.len(), 2);
+        assert_eq!(
+            multipart.parts[1].headers.get("content-type"),
+            Some(&"application/pdf".to_string())
+        );
+        assert!(requests[0].body.is_some());
+    }
+
+    #[test]
+    fn test_parse_non_multipart_body_has_no_parts() {
+        let content = r#"POST https://example.com/api
+Content-Type: application/json
+
+{"a": 1}"#;
+
+        let requests = parse_http_file(content);
+        assert!(requests[0].multipart.is_none());
+    }
+
+    #[rstest]
+    #[case("GET file:///etc/passwd", 0, 4, 22, "Unsupported URL scheme")]
+    #[case("GET not-a-url", 0, 4, 13, "Invalid URL")]
+    #[case("  FETCH https://example.com", 0, 2, 7, "Unknown HTTP method 'FETCH'")]
+    #[case(
+        "GET https://example.com\nAccept application/json",
+        1,
+        0,
+        23,
+        "Invalid header line"
+    )]
+    #[case(
+        "# ünïcode\nGET https://example.com\nÄccept",
+        2,
+        0,
+        6,
+        "Invalid header line"
+    )]
+    fn test_parse_document_reports_errors(
+        #[case] content: &str,
+        #[case] line: usize,
+        #[case] start_column: usize,
+        #[case] end_column: usize,
+        #[case] message: &str,
+    ) {
+        let document = parse_document(content);
+
+        assert_eq!(document.errors.len(), 1);
+        let error = &document.errors[0];
+        assert_eq!(
+            (error.line, error.start_column, error.end_column),
+            (line, start_column, end_column)
+        );
+        assert!(
+            error.message.contains(message),
+            "unexpected message: {}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn test_parse_document_reports_long_urls() {
+        let content = format!("GET https://example.com/{}", "a".repeat(2048));
+        let document = parse_document(&content);
+
+        assert!(document.requests.is_empty());
+        assert!(document.errors[0].message.contains("URL too long"));
+    }
+
+    #[test]
+    fn test_parse_document_reports_errors_from_every_block() {
+        let content = r#"GET javascript:alert(1)
+###
+POST https://valid.com/api
+X-Broken
+
+{"not": "a header"}
+###
+GET ftp://example.com"#;
+
+        let document = parse_document(content);
+
+        assert_eq!(document.requests.len(), 1);
+        let lines: Vec<usize> = document.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![0, 3, 7]);
+    }
+
+    #[rstest]
+    #[case("@host = example.com\nGET https://{{host}}/")]
+    #[case("GET {{baseUrl}}/users")]
+    #[case("# just a comment\nGET https://example.com\n\nbody without colon")]
+    fn test_parse_document_valid_requests_have_no_errors(#[case] content: &str) {
+        assert!(parse_document(content).errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_document_records_declaration_lines() {
+        let content = "@host = a.com\n@host = b.com\nGET https://{{host}}/\n###\n@token = t";
+        let document = parse_document(content);
+
+        let lines: Vec<(&str, usize)> = document
+            .declarations
+            .iter()
+            .map(|declaration| (declaration.name.as_str(), declaration.line))
+            .collect();
+        assert_eq!(lines, vec![("host", 0), ("host", 1), ("token", 4)]);
+        assert_eq!(document.variables_at(3)["host"], "b.com");
+        assert_eq!(document.declaration("host", 1).map(|d| d.line), Some(0));
+        assert_eq!(document.declaration("token", 4), None);
+    }
+
+    #[test]
+    fn test_parse_separator_titles() {
+        let content = r#"GET https://example.com/first
+### Create user
+POST https://example.com/users
+###
+GET https://example.com/untitled"#;
+
+        let requests = parse_http_file(content);
+        let titles: Vec<Option<&str>> = requests.iter().map(|r| r.title.as_deref()).collect();
+
+        assert_eq!(titles, vec![None, Some("Create user"), None]);
+    }
+
+    #[test]
+    fn test_parse_records_block_and_body_lines() {
+        let content = r#"@host = example.com
+
+GET https://example.com/first
+
+### Create
+POST https://example.com/users
+Content-Type: application/json
+
+
+{
+  "name": "test"
+}
+
+###
+GET https://example.com/no-body
+"#;
+
+        let requests = parse_http_file(content);
+        let ranges: Vec<(Range<usize>, Option<Range<usize>>)> = requests
+            .iter()
+            .map(|r| (r.block_lines.clone(), r.body_lines.clone()))
+            .collect();
+
+        assert_eq!(
+            ranges,
+            vec![(0..3, None), (4..12, Some(9..12)), (13..15, None)]
+        );
+    }
 }
@@ -0,0 +1,262 @@
+use crate::headers::Headers;
+use crate::multipart;
+use encoding_rs::{Encoding, UTF_8};
+
+/// Bytes of a binary body shown in its hex preview
+const PREVIEW_BYTES: usize = 256;
+
+/// Bytes per hex preview line
+const BYTES_PER_LINE: usize = 16;
+
+/// Media types outside `text/*` whose bodies are text
+const TEXT_MEDIA_TYPES: [&str; 9] = [
+    "application/json",
+    "application/xml",
+    "application/javascript",
+    "application/x-www-form-urlencoded",
+    "application/graphql",
+    "application/yaml",
+    "application/x-yaml",
+    "application/toml",
+    "application/sql",
+];
+
+/// File extensions for saved bodies, by media type
+const EXTENSIONS: [(&str, &str); 22] = [
+    ("application/gzip", "gz"),
+    ("application/json", "json"),
+    ("application/msword", "doc"),
+    ("application/octet-stream", "bin"),
+    ("application/pdf", "pdf"),
+    ("application/protobuf", "pb"),
+    ("application/wasm", "wasm"),
+    ("application/x-protobuf", "pb"),
+    ("application/xml", "xml"),
+    ("application/zip", "zip"),
+    ("audio/mpeg", "mp3"),
+    ("audio/wav", "wav"),
+    ("font/woff", "woff"),
+    ("font/woff2", "woff2"),
+    ("image/gif", "gif"),
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/svg+xml", "svg"),
+    ("image/webp", "webp"),
+    ("image/x-icon", "ico"),
+    ("text/plain", "txt"),
+    ("video/mp4", "mp4"),
+];
+
+/// The lowercase `type/subtype` of a Content-Type header, without parameters
+fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Whether a body with these headers can be shown as text. Compressed bodies are binary
+/// whatever their type, and bodies without a type are text when they are UTF-8 without
+/// NUL bytes.
+pub fn is_text(headers: &Headers, bytes: &[u8]) -> bool {
+    let compressed = headers
+        .get("content-encoding")
+        .is_some_and(|encoding| !encoding.trim().eq_ignore_ascii_case("identity"));
+    if compressed {
+        return false;
+    }
+
+    let Some(content_type) = headers.get("content-type") else {
+        return std::str::from_utf8(bytes).is_ok_and(|text| !text.contains('\0'));
+    };
+    let media_type = media_type(content_type);
+    media_type.starts_with("text/")
+        || media_type.ends_with("+json")
+        || media_type.ends_with("+xml")
+        || TEXT_MEDIA_TYPES.contains(&media_type.as_str())
+        || multipart::header_param(content_type, "charset").is_some()
+}
+
+/// Decodes a text body with the charset of its Content-Type, or UTF-8 when it has none or
+/// names an unknown one. A byte order mark takes precedence, as it does in browsers.
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|content_type| multipart::header_param(content_type, "charset"))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// File extension for a saved body, with `.gz` or `.br` added for compressed bodies
+pub fn extension(headers: &Headers) -> String {
+    let media_type = headers
+        .get("content-type")
+        .map(|content_type| media_type(content_type))
+        .unwrap_or_default();
+    let mut extension = EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == media_type)
+        .map_or("bin", |(_, extension)| extension)
+        .to_string();
+
+    match headers
+        .get("content-encoding")
+        .map(|e| e.trim().to_ascii_lowercase())
+    {
+        Some(encoding) if encoding == "gzip" && extension != "gz" => extension.push_str(".gz"),
+        Some(encoding) if encoding == "br" => extension.push_str(".br"),
+        _ => {}
+    }
+    extension
+}
+
+/// A size in bytes, with a rounded figure for larger sizes
+pub fn format_size(len: usize) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+
+    let mut size = len as f64;
+    let mut unit = None;
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = Some(next);
+    }
+
+    match unit {
+        Some(unit) => format!("{:.1} {} ({} bytes)", size, unit, len),
+        None if len == 1 => "1 byte".to_string(),
+        None => format!("{} bytes", len),
+    }
+}
+
+/// The first bytes of a body in `hexdump -C` layout, noting how many bytes were left out
+pub fn hex_preview(bytes: &[u8]) -> String {
+    let shown = &bytes[..bytes.len().min(PREVIEW_BYTES)];
+    let mut lines: Vec<String> = shown
+        .chunks(BYTES_PER_LINE)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hex: Vec<String> = (0..BYTES_PER_LINE)
+                .map(|col| {
+                    chunk
+                        .get(col)
+                        .map_or("  ".to_string(), |b| format!("{:02x}", b))
+                })
+                .collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+
+            format!(
+                "{:08x}  {}  {}  |{}|",
+                idx * BYTES_PER_LINE,
+                hex[..8].join(" "),
+                hex[8..].join(" "),
+                ascii
+            )
+        })
+        .collect();
+
+    if bytes.len() > shown.len() {
+        lines.push(format!("… {} more", format_size(bytes.len() - shown.len())));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn headers(pairs: &[(&str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for (name, value) in pairs {
+            headers.append(*name, *value);
+        }
+        headers
+    }
+
+    #[rstest]
+    #[case(&[("Content-Type", "text/html; charset=utf-8")], b"<p>", true)]
+    #[case(&[("Content-Type", "application/problem+json")], b"{}", true)]
+    #[case(&[("Content-Type", "application/xml")], b"<a/>", true)]
+    #[case(&[("Content-Type", "application/octet-stream; charset=latin1")], b"abc", true)]
+    #[case(&[("Content-Type", "image/png")], b"\x89PNG", false)]
+    #[case(&[("Content-Type", "application/json"), ("Content-Encoding", "gzip")], b"\x1f\x8b", false)]
+    #[case(&[("Content-Type", "text/plain"), ("Content-Encoding", "identity")], b"hi", true)]
+    #[case(&[], b"plain words", true)]
+    #[case(&[], b"nul\0byte", false)]
+    #[case(&[], b"\xff\xfe", false)]
+    fn test_is_text(#[case] pairs: &[(&str, &str)], #[case] bytes: &[u8], #[case] expected: bool) {
+        assert_eq!(is_text(&headers(pairs), bytes), expected);
+    }
+
+    #[rstest]
+    #[case(b"caf\xc3\xa9", None, "café")]
+    #[case(b"caf\xe9", Some("text/plain; charset=ISO-8859-1"), "café")]
+    #[case(b"caf\xe9", Some("text/plain; charset=\"windows-1252\""), "café")]
+    #[case(b"\x82\xb1\x82\xf1", Some("text/plain; charset=Shift_JIS"), "こん")]
+    #[case(b"caf\xc3\xa9", Some("text/plain; charset=unknown"), "café")]
+    #[case(b"\xef\xbb\xbfbom", Some("text/plain; charset=latin1"), "bom")]
+    fn test_decode(
+        #[case] bytes: &[u8],
+        #[case] content_type: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(decode(bytes, content_type), expected);
+    }
+
+    #[rstest]
+    #[case(&[("Content-Type", "image/png")], "png")]
+    #[case(&[("Content-Type", "Application/PDF; name=report.pdf")], "pdf")]
+    #[case(&[("Content-Type", "application/json"), ("Content-Encoding", "gzip")], "json.gz")]
+    #[case(&[("Content-Type", "application/gzip"), ("Content-Encoding", "gzip")], "gz")]
+    #[case(&[("Content-Encoding", "br")], "bin.br")]
+    #[case(&[("Content-Type", "application/x-custom")], "bin")]
+    fn test_extension(#[case] pairs: &[(&str, &str)], #[case] expected: &str) {
+        assert_eq!(extension(&headers(pairs)), expected);
+    }
+
+    #[rstest]
+    #[case(0, "0 bytes")]
+    #[case(1, "1 byte")]
+    #[case(1023, "1023 bytes")]
+    #[case(12_594, "12.3 KB (12594 bytes)")]
+    #[case(5 * 1024 * 1024, "5.0 MB (5242880 bytes)")]
+    fn test_format_size(#[case] len: usize, #[case] expected: &str) {
+        assert_eq!(format_size(len), expected);
+    }
+
+    #[test]
+    fn test_hex_preview() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\x01";
+
+        assert_eq!(
+            hex_preview(png),
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+             00000010  00 01                                             |..|"
+        );
+    }
+
+    #[test]
+    fn test_hex_preview_notes_truncation() {
+        let bytes = vec![b'a'; PREVIEW_BYTES + 10];
+        let preview = hex_preview(&bytes);
+
+        assert_eq!(preview.lines().count(), PREVIEW_BYTES / BYTES_PER_LINE + 1);
+        assert!(preview.starts_with("00000000  61 61"));
+        assert!(preview.ends_with("… 10 bytes more"));
+    }
+}
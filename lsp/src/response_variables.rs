@@ -0,0 +1,359 @@
+use crate::executor::HttpResponse;
+use roxmltree::{Document, Node};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+/// A `{{name.response...}}` reference to the response of a named request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseReference<'a> {
+    pub request_name: &'a str,
+    pub selector: ResponseSelector<'a>,
+}
+
+/// The part of a response a reference selects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseSelector<'a> {
+    /// The whole body, or the part matched by a JSONPath (`$...`) or XPath (`/...`) expression
+    Body(Option<&'a str>),
+    Header(&'a str),
+}
+
+/// Parses `login.response.body.$.token` or `login.response.headers.Location`
+pub fn parse_reference(expression: &str) -> Option<ResponseReference<'_>> {
+    let (request_name, rest) = expression.split_once(".response.")?;
+    if request_name.is_empty()
+        || !request_name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return None;
+    }
+
+    let selector = if rest == "body" || rest == "body.*" {
+        ResponseSelector::Body(None)
+    } else if let Some(path) = rest.strip_prefix("body.") {
+        ResponseSelector::Body(Some(path))
+    } else if let Some(header) = rest.strip_prefix("headers.") {
+        ResponseSelector::Header(header)
+    } else {
+        return None;
+    };
+
+    Some(ResponseReference {
+        request_name,
+        selector,
+    })
+}
+
+/// Extracts the value a reference selects from a stored response
+pub fn resolve(reference: &ResponseReference, response: &HttpResponse) -> Result<String, String> {
+    match reference.selector {
+        ResponseSelector::Header(name) => response.headers.get(name).cloned().ok_or_else(|| {
+            format!(
+                "Header '{}' not found in response of '{}'",
+                name, reference.request_name
+            )
+        }),
+        ResponseSelector::Body(None) => Ok(response.body.clone()),
+        ResponseSelector::Body(Some(path)) if path.starts_with('$') => {
+            select_json_path(&response.body, path)
+        }
+        ResponseSelector::Body(Some(path)) if path.starts_with('/') => {
+            select_xpath(&response.body, path)
+        }
+        ResponseSelector::Body(Some(path)) => Err(format!(
+            "Unsupported body selector '{}': expected a JSONPath ($...) or XPath (/...)",
+            path
+        )),
+    }
+}
+
+/// Evaluates a JSONPath query. A single string match is returned unquoted, other single
+/// matches as JSON, and multiple matches as a JSON array.
+pub fn select_json_path(body: &str, path: &str) -> Result<String, String> {
+    let json: Value =
+        serde_json::from_str(body).map_err(|e| format!("Response body is not JSON: {}", e))?;
+    let query = JsonPath::parse(path).map_err(|e| format!("Invalid JSONPath '{}': {}", path, e))?;
+
+    let matches = query.query(&json).all();
+    match matches.as_slice() {
+        [] => Err(format!("JSONPath '{}' matched nothing", path)),
+        [Value::String(s)] => Ok(s.clone()),
+        [value] => Ok(value.to_string()),
+        values => Ok(Value::Array(values.iter().map(|v| (*v).clone()).collect()).to_string()),
+    }
+}
+
+/// Evaluates a subset of XPath: `/` and `//` steps, `*`, 1-based `[n]` predicates and a
+/// trailing `@attribute` or `text()`. Returns the string value of the first match.
+pub fn select_xpath(body: &str, path: &str) -> Result<String, String> {
+    let document = Document::parse(body).map_err(|e| format!("Response body is not XML: {}", e))?;
+    let steps = parse_xpath(path).ok_or_else(|| format!("Unsupported XPath '{}'", path))?;
+
+    let mut nodes = vec![document.root()];
+    for step in &steps {
+        nodes = match step.test {
+            XPathTest::Element(name) => select_elements(&nodes, step.descendant, name, step.index),
+            XPathTest::Attribute(name) => {
+                let value = nodes
+                    .iter()
+                    .find_map(|node| node.attribute(name))
+                    .ok_or_else(|| format!("XPath '{}' matched nothing", path))?;
+                return Ok(value.to_string());
+            }
+            XPathTest::Text => {
+                let node = nodes
+                    .first()
+                    .ok_or_else(|| format!("XPath '{}' matched nothing", path))?;
+                return Ok(node
+                    .children()
+                    .filter(|child| child.is_text())
+                    .filter_map(|child| child.text())
+                    .collect());
+            }
+        };
+    }
+
+    let node = nodes
+        .first()
+        .ok_or_else(|| format!("XPath '{}' matched nothing", path))?;
+    Ok(node
+        .descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect())
+}
+
+struct XPathStep<'a> {
+    descendant: bool,
+    test: XPathTest<'a>,
+    index: Option<usize>,
+}
+
+enum XPathTest<'a> {
+    Element(&'a str),
+    Attribute(&'a str),
+    Text,
+}
+
+fn parse_xpath(path: &str) -> Option<Vec<XPathStep<'_>>> {
+    let mut steps = Vec::new();
+    let mut rest = path;
+
+    while !rest.is_empty() {
+        let descendant = rest.starts_with("//");
+        rest = rest.strip_prefix("//").or_else(|| rest.strip_prefix('/'))?;
+
+        let end = rest.find('/').unwrap_or(rest.len());
+        let segment = &rest[..end];
+        rest = &rest[end..];
+
+        let (name, index) = match segment.split_once('[') {
+            Some((name, predicate)) => {
+                let index: usize = predicate.strip_suffix(']')?.trim().parse().ok()?;
+                (name, Some(index.checked_sub(1)?))
+            }
+            None => (segment, None),
+        };
+
+        let test = if name == "text()" {
+            XPathTest::Text
+        } else if let Some(attribute) = name.strip_prefix('@') {
+            XPathTest::Attribute(attribute)
+        } else if !name.is_empty() {
+            XPathTest::Element(name)
+        } else {
+            return None;
+        };
+
+        // Attributes and text() must be the last step
+        if !matches!(test, XPathTest::Element(_)) && !rest.is_empty() {
+            return None;
+        }
+
+        steps.push(XPathStep {
+            descendant,
+            test,
+            index,
+        });
+    }
+
+    (!steps.is_empty()).then_some(steps)
+}
+
+fn select_elements<'a, 'input>(
+    context: &[Node<'a, 'input>],
+    descendant: bool,
+    name: &str,
+    index: Option<usize>,
+) -> Vec<Node<'a, 'input>> {
+    let parents: Vec<Node> = if descendant {
+        context.iter().flat_map(|node| node.descendants()).collect()
+    } else {
+        context.to_vec()
+    };
+
+    let mut selected = Vec::new();
+    for parent in parents {
+        let children = parent
+            .children()
+            .filter(|child| child.is_element())
+            .filter(|child| name == "*" || child.tag_name().name() == name);
+        match index {
+            Some(index) => selected.extend(children.skip(index).take(1)),
+            None => selected.extend(children),
+        }
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
+    use rstest::rstest;
+
+    fn create_test_response(content_type: &str, body: &str) -> HttpResponse {
+        let mut headers = Headers::new();
+        headers.insert("content-type".to_string(), content_type.to_string());
+        headers.insert("location".to_string(), "/users/42".to_string());
+
+        HttpResponse {
+            status: 200,
+            status_text: "OK".to_string(),
+            headers,
+            body: body.to_string(),
+            raw_body: body.into(),
+            duration_ms: 10,
+            timings: Timings::default(),
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "login.response.body.$.token",
+        "login",
+        ResponseSelector::Body(Some("$.token"))
+    )]
+    #[case("login.response.body.*", "login", ResponseSelector::Body(None))]
+    #[case("login.response.body", "login", ResponseSelector::Body(None))]
+    #[case(
+        "get-user.response.body.//user/name",
+        "get-user",
+        ResponseSelector::Body(Some("//user/name"))
+    )]
+    #[case(
+        "create_user.response.headers.Location",
+        "create_user",
+        ResponseSelector::Header("Location")
+    )]
+    fn test_parse_reference(
+        #[case] expression: &str,
+        #[case] expected_name: &str,
+        #[case] expected_selector: ResponseSelector,
+    ) {
+        let reference = parse_reference(expression).expect("Expected a response reference");
+        assert_eq!(reference.request_name, expected_name);
+        assert_eq!(reference.selector, expected_selector);
+    }
+
+    #[rstest]
+    #[case("baseUrl")]
+    #[case("$uuid")]
+    #[case("login.response")]
+    #[case("login.response.status")]
+    #[case(".response.body")]
+    #[case("bad name.response.body")]
+    fn test_parse_reference_rejects_other_expressions(#[case] expression: &str) {
+        assert!(parse_reference(expression).is_none());
+    }
+
+    #[rstest]
+    #[case("$.token", "abc123")]
+    #[case("$.user.id", "42")]
+    #[case("$.user", r#"{"id":42,"name":"Jane"}"#)]
+    #[case("$.roles[0]", "admin")]
+    #[case("$.roles[*]", r#"["admin","editor"]"#)]
+    #[case("$['token']", "abc123")]
+    #[case("$.active", "true")]
+    fn test_select_json_path(#[case] path: &str, #[case] expected: &str) {
+        let body = r#"{"token": "abc123", "user": {"id": 42, "name": "Jane"}, "roles": ["admin", "editor"], "active": true}"#;
+        assert_eq!(select_json_path(body, path), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case(r#"{"a": 1}"#, "$.missing", "matched nothing")]
+    #[case(r#"{"a": 1}"#, "$[", "Invalid JSONPath")]
+    #[case("<xml/>", "$.a", "not JSON")]
+    fn test_select_json_path_errors(
+        #[case] body: &str,
+        #[case] path: &str,
+        #[case] expected_error: &str,
+    ) {
+        let error = select_json_path(body, path).unwrap_err();
+        assert!(
+            error.contains(expected_error),
+            "Unexpected error: {}",
+            error
+        );
+    }
+
+    #[rstest]
+    #[case("/response/token", "abc123")]
+    #[case("//token", "abc123")]
+    #[case("/response/user/@id", "42")]
+    #[case("//user/name/text()", "Jane")]
+    #[case("/response/roles/role[2]", "editor")]
+    #[case("//role", "admin")]
+    #[case("/response/*[1]", "abc123")]
+    #[case("/response/user", "Jane")]
+    fn test_select_xpath(#[case] path: &str, #[case] expected: &str) {
+        let body = r#"<?xml version="1.0"?>
+<response>
+  <token>abc123</token>
+  <user id="42"><name>Jane</name></user>
+  <roles><role>admin</role><role>editor</role></roles>
+</response>"#;
+        assert_eq!(select_xpath(body, path), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case("<a><b>1</b></a>", "/a/c", "matched nothing")]
+    #[case("<a><b>1</b></a>", "/a/b[0]", "Unsupported XPath")]
+    #[case("<a><b>1</b></a>", "/a/@id/b", "Unsupported XPath")]
+    #[case("<a><b>1</b></a>", "a/b", "Unsupported XPath")]
+    #[case("{}", "/a", "not XML")]
+    fn test_select_xpath_errors(
+        #[case] body: &str,
+        #[case] path: &str,
+        #[case] expected_error: &str,
+    ) {
+        let error = select_xpath(body, path).unwrap_err();
+        assert!(
+            error.contains(expected_error),
+            "Unexpected error: {}",
+            error
+        );
+    }
+
+    #[rstest]
+    #[case("login.response.body.$.token", Ok("abc"))]
+    #[case("login.response.body.*", Ok(r#"{"token": "abc"}"#))]
+    #[case("login.response.headers.Location", Ok("/users/42"))]
+    #[case("login.response.headers.content-type", Ok("application/json"))]
+    #[case(
+        "login.response.headers.X-Missing",
+        Err("Header 'X-Missing' not found in response of 'login'")
+    )]
+    fn test_resolve(#[case] expression: &str, #[case] expected: std::result::Result<&str, &str>) {
+        let response = create_test_response("application/json", r#"{"token": "abc"}"#);
+        let reference = parse_reference(expression).expect("Expected a response reference");
+
+        assert_eq!(
+            resolve(&reference, &response),
+            expected.map(str::to_string).map_err(str::to_string)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::executor::HttpResponse;
+use crate::parser::HttpRequest;
+
+/// How one request of a run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Answered with a status below 400; holds the response summary
+    Passed(String),
+    /// Answered with an error status, or not sent at all; holds the reason
+    Failed(String),
+    /// Not sent because an earlier request failed
+    Skipped,
+}
+
+/// A request of a run, labelled by its request line, and how it ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunResult {
+    pub request: String,
+    pub outcome: Outcome,
+}
+
+impl RunResult {
+    pub fn new(request: &HttpRequest, outcome: Outcome) -> Self {
+        RunResult {
+            request: format!("{} {}", request.method, request.url),
+            outcome,
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, Outcome::Failed(_))
+    }
+}
+
+/// Requests whose block overlaps the lines `start_line..=end_line`, in document order
+pub fn requests_in_range(
+    requests: &[HttpRequest],
+    start_line: usize,
+    end_line: usize,
+) -> Vec<&HttpRequest> {
+    requests
+        .iter()
+        .filter(|request| {
+            request.block_lines.start <= end_line && start_line < request.block_lines.end
+        })
+        .collect()
+}
+
+/// Whether a response counts as a success in a run: error statuses fail it
+pub fn outcome(response: &HttpResponse) -> Outcome {
+    if response.status >= 400 {
+        Outcome::Failed(response.summary())
+    } else {
+        Outcome::Passed(response.summary())
+    }
+}
+
+/// A count of each outcome followed by one line per request
+pub fn summary(results: &[RunResult]) -> String {
+    let count = |matches: fn(&Outcome) -> bool| {
+        results
+            .iter()
+            .filter(|result| matches(&result.outcome))
+            .count()
+    };
+
+    let mut summary = format!(
+        "{} requests: {} passed, {} failed, {} skipped",
+        results.len(),
+        count(|outcome| matches!(outcome, Outcome::Passed(_))),
+        count(|outcome| matches!(outcome, Outcome::Failed(_))),
+        count(|outcome| matches!(outcome, Outcome::Skipped)),
+    );
+    for result in results {
+        summary.push_str(&match &result.outcome {
+            Outcome::Passed(detail) => format!("\n✓ {} - {}", result.request, detail),
+            Outcome::Failed(reason) => format!("\n✗ {} - {}", result.request, reason),
+            Outcome::Skipped => format!("\n- {} (skipped)", result.request),
+        });
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::Headers;
+    use crate::parser::parse_document;
+    use crate::timing::Timings;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "GET https://example.com/a
+
+###
+POST https://example.com/b
+Content-Type: text/plain
+
+body
+
+###
+DELETE https://example.com/c";
+
+    fn response(status: u16, status_text: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            status_text: status_text.to_string(),
+            headers: Headers::new(),
+            body: String::new(),
+            raw_body: Vec::new(),
+            duration_ms: 3,
+            timings: Timings::default(),
+        }
+    }
+
+    #[rstest]
+    #[case(0, 9, vec!["/a", "/b", "/c"])]
+    #[case(0, 0, vec!["/a"])]
+    #[case(1, 2, vec!["/b"])]
+    #[case(6, 9, vec!["/b", "/c"])]
+    #[case(10, 20, vec![])]
+    fn test_requests_in_range(
+        #[case] start: usize,
+        #[case] end: usize,
+        #[case] expected: Vec<&str>,
+    ) {
+        let document = parse_document(DOCUMENT);
+        let urls: Vec<&str> = requests_in_range(&document.requests, start, end)
+            .iter()
+            .map(|request| request.url.trim_start_matches("https://example.com"))
+            .collect();
+
+        assert_eq!(urls, expected);
+    }
+
+    #[rstest]
+    #[case(200, "OK", Outcome::Passed("200 OK (3ms)".to_string()))]
+    #[case(302, "Found", Outcome::Passed("302 Found (3ms)".to_string()))]
+    #[case(404, "Not Found", Outcome::Failed("404 Not Found (3ms)".to_string()))]
+    fn test_outcome(#[case] status: u16, #[case] status_text: &str, #[case] expected: Outcome) {
+        assert_eq!(outcome(&response(status, status_text)), expected);
+    }
+
+    #[test]
+    fn test_summary() {
+        let document = parse_document(DOCUMENT);
+        let requests = &document.requests;
+        let results = vec![
+            RunResult::new(&requests[0], Outcome::Passed("200 OK (3ms)".to_string())),
+            RunResult::new(&requests[1], Outcome::Failed("Request failed".to_string())),
+            RunResult::new(&requests[2], Outcome::Skipped),
+        ];
+
+        assert_eq!(
+            summary(&results),
+            "3 requests: 1 passed, 1 failed, 1 skipped\n\
+             ✓ GET https://example.com/a - 200 OK (3ms)\n\
+             ✗ POST https://example.com/b - Request failed\n\
+             - DELETE https://example.com/c (skipped)"
+        );
+        assert!(results[1].failed());
+    }
+}
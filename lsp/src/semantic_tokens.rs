@@ -0,0 +1,453 @@
+use crate::parser::{self, ParsedDocument};
+use crate::response_variables;
+use crate::system_variables::SYSTEM_VARIABLES;
+use crate::variables::{self, Placeholder};
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+
+/// Token types in legend order, indexed by [`TokenType`]
+const TOKEN_TYPES: [SemanticTokenType; 8] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::STRING,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::DECORATOR,
+    SemanticTokenType::COMMENT,
+];
+
+/// Token modifiers in legend order; a token's modifiers are a bitset of these
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 2] = [
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::new("unresolved"),
+];
+
+const DECLARATION: u32 = 1;
+const UNRESOLVED: u32 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenType {
+    /// Request methods and HTTP versions
+    Method,
+    /// URL scheme and authority
+    Host,
+    /// URL paths, query values, header values and directive values
+    Text,
+    QueryKey,
+    HeaderName,
+    Variable,
+    Directive,
+    Comment,
+}
+
+/// The legend advertised in the server capabilities
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A token at a byte range of a line, before LSP's relative encoding
+#[derive(Debug, PartialEq, Eq)]
+struct Token {
+    line: usize,
+    range: Range<usize>,
+    kind: TokenType,
+    modifiers: u32,
+}
+
+struct Tokenizer<'a> {
+    document: &'a ParsedDocument,
+    environment_variables: &'a HashMap<String, String>,
+    tokens: Vec<Token>,
+}
+
+/// Semantic tokens for the whole document. Variables that can't be resolved from the file,
+/// the active environment, system variables or a named request are marked `unresolved`.
+pub fn semantic_tokens(
+    text: &str,
+    document: &ParsedDocument,
+    environment_variables: &HashMap<String, String>,
+) -> Vec<SemanticToken> {
+    encode(text, tokenize(text, document, environment_variables))
+}
+
+fn tokenize(
+    text: &str,
+    document: &ParsedDocument,
+    environment_variables: &HashMap<String, String>,
+) -> Vec<Token> {
+    let mut tokenizer = Tokenizer {
+        document,
+        environment_variables,
+        tokens: Vec::new(),
+    };
+    let mut request_seen = false;
+    let mut in_body = false;
+
+    for (idx, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        let indent = line.len() - line.trim_start().len();
+        let content = indent..indent + trimmed.len();
+
+        if trimmed.starts_with("###") {
+            request_seen = false;
+            in_body = false;
+            tokenizer.push(idx, content, TokenType::Comment, 0);
+            continue;
+        }
+        if !in_body && (trimmed.starts_with('#') || trimmed.starts_with("//")) {
+            tokenizer.comment(idx, line, content);
+            continue;
+        }
+
+        let placeholders = variables::placeholder_spans(line);
+        tokenizer.placeholders(idx, &placeholders);
+
+        if in_body {
+            continue;
+        }
+        if request_seen {
+            if trimmed.is_empty() {
+                in_body = true;
+            } else if let Some(colon) = line.find(':') {
+                let name = &line[indent..colon];
+                let value = &line[colon + 1..];
+                let value_start = colon + 1 + (value.len() - value.trim_start().len());
+                tokenizer.push_text(
+                    idx,
+                    &placeholders,
+                    indent..indent + name.trim_end().len(),
+                    TokenType::HeaderName,
+                );
+                tokenizer.push_text(
+                    idx,
+                    &placeholders,
+                    value_start..content.end,
+                    TokenType::Text,
+                );
+            }
+        } else if let Some((name, _)) = variables::parse_variable_declaration(trimmed) {
+            let name_start = indent + trimmed.find(&name).unwrap_or(1);
+            tokenizer.push(
+                idx,
+                name_start..name_start + name.len(),
+                TokenType::Variable,
+                DECLARATION,
+            );
+            if let Some(equals) = line.find('=') {
+                let value = &line[equals + 1..];
+                let value_start = equals + 1 + (value.len() - value.trim_start().len());
+                tokenizer.push_text(
+                    idx,
+                    &placeholders,
+                    value_start..content.end,
+                    TokenType::Text,
+                );
+            }
+        } else if parser::is_request_line(trimmed) {
+            request_seen = true;
+            tokenizer.request_line(idx, line, &placeholders);
+        }
+    }
+
+    let mut tokens = tokenizer.tokens;
+    tokens.sort_by_key(|token| (token.line, token.range.start));
+    tokens
+}
+
+impl Tokenizer<'_> {
+    fn push(&mut self, line: usize, range: Range<usize>, kind: TokenType, modifiers: u32) {
+        if !range.is_empty() {
+            self.tokens.push(Token {
+                line,
+                range,
+                kind,
+                modifiers,
+            });
+        }
+    }
+
+    /// Pushes the parts of `range` that aren't covered by a placeholder
+    fn push_text(
+        &mut self,
+        line: usize,
+        placeholders: &[Placeholder],
+        range: Range<usize>,
+        kind: TokenType,
+    ) {
+        let mut start = range.start;
+        for placeholder in placeholders {
+            if placeholder.end <= start || placeholder.start >= range.end {
+                continue;
+            }
+            self.push(line, start..placeholder.start.max(start), kind, 0);
+            start = placeholder.end;
+        }
+        self.push(line, start..range.end.max(start), kind, 0);
+    }
+
+    fn placeholders(&mut self, line: usize, placeholders: &[Placeholder]) {
+        for placeholder in placeholders {
+            let modifiers = if self.is_resolvable(placeholder.expression, line) {
+                0
+            } else {
+                UNRESOLVED
+            };
+            self.push(
+                line,
+                placeholder.start..placeholder.end,
+                TokenType::Variable,
+                modifiers,
+            );
+        }
+    }
+
+    /// Comments, with `# @directive value` lines split into the directive and its value
+    fn comment(&mut self, idx: usize, line: &str, content: Range<usize>) {
+        let marker_len = if line[content.clone()].starts_with("//") {
+            2
+        } else {
+            1
+        };
+        let marker_end = content.start + marker_len;
+        let rest = &line[marker_end..content.end];
+        let directive_start = marker_end + (rest.len() - rest.trim_start().len());
+
+        if !line[directive_start..].starts_with('@') {
+            self.push(idx, content, TokenType::Comment, 0);
+            return;
+        }
+
+        let directive_end = line[directive_start..content.end]
+            .find(char::is_whitespace)
+            .map_or(content.end, |end| directive_start + end);
+        let value = &line[directive_end..content.end];
+        let value_start = directive_end + (value.len() - value.trim_start().len());
+
+        self.push(idx, content.start..marker_end, TokenType::Comment, 0);
+        self.push(idx, directive_start..directive_end, TokenType::Directive, 0);
+        self.push(idx, value_start..content.end, TokenType::Text, 0);
+    }
+
+    fn request_line(&mut self, idx: usize, line: &str, placeholders: &[Placeholder]) {
+        let parts = parser::split_request_line(line.trim());
+        let offset = |part: &str| part.as_ptr() as usize - line.as_ptr() as usize;
+
+        let method = parts[0];
+        self.push(
+            idx,
+            offset(method)..offset(method) + method.len(),
+            TokenType::Method,
+            0,
+        );
+        for version in parts.iter().skip(2) {
+            self.push(
+                idx,
+                offset(version)..offset(version) + version.len(),
+                TokenType::Method,
+                0,
+            );
+        }
+
+        let url = parts[1];
+        let base = offset(url);
+        let (path_part, query) = match url.find('?') {
+            Some(question) => (&url[..question], Some(question + 1)),
+            None => (url, None),
+        };
+        let authority_start = path_part.find("://").map_or(0, |scheme| scheme + 3);
+        let authority_end = path_part[authority_start..]
+            .find('/')
+            .map_or(path_part.len(), |slash| authority_start + slash);
+
+        self.push_text(
+            idx,
+            placeholders,
+            base..base + authority_end,
+            TokenType::Host,
+        );
+        self.push_text(
+            idx,
+            placeholders,
+            base + authority_end..base + path_part.len(),
+            TokenType::Text,
+        );
+
+        let Some(mut pair_start) = query else {
+            return;
+        };
+        for pair in url[pair_start..].split('&') {
+            let key_end = pair.find('=').unwrap_or(pair.len());
+            let start = base + pair_start;
+            self.push_text(
+                idx,
+                placeholders,
+                start..start + key_end,
+                TokenType::QueryKey,
+            );
+            self.push_text(
+                idx,
+                placeholders,
+                (start + key_end + 1).min(start + pair.len())..start + pair.len(),
+                TokenType::Text,
+            );
+            pair_start += pair.len() + 1;
+        }
+    }
+
+    fn is_resolvable(&self, expression: &str, line: usize) -> bool {
+        if self.document.declaration(expression, line).is_some()
+            || self.environment_variables.contains_key(expression)
+        {
+            return true;
+        }
+
+        if expression.starts_with('$') {
+            let name = expression.split_whitespace().next();
+            return SYSTEM_VARIABLES
+                .iter()
+                .any(|(usage, _)| usage.split(' ').next() == name);
+        }
+
+        response_variables::parse_reference(expression).is_some_and(|reference| {
+            self.document
+                .requests
+                .iter()
+                .any(|request| request.name.as_deref() == Some(reference.request_name))
+        })
+    }
+}
+
+/// Converts tokens to LSP's encoding: positions relative to the previous token, in UTF-16
+fn encode(text: &str, tokens: Vec<Token>) -> Vec<SemanticToken> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut encoded = Vec::with_capacity(tokens.len());
+    let mut previous_line = 0;
+    let mut previous_start = 0;
+
+    for token in tokens {
+        let line = lines[token.line];
+        let start = parser::utf16_len(&line[..token.range.start]) as u32;
+        let length = parser::utf16_len(&line[token.range.clone()]) as u32;
+        let delta_line = (token.line - previous_line) as u32;
+        let delta_start = if delta_line == 0 {
+            start - previous_start
+        } else {
+            start
+        };
+
+        encoded.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: token.kind as u32,
+            token_modifiers_bitset: token.modifiers,
+        });
+        previous_line = token.line;
+        previous_start = start;
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_document;
+
+    const DOCUMENT: &str = "@host = example.com
+# @name login
+POST https://{{host}}/api/users?page=2&q={{query}} HTTP/1.1
+Content-Type: application/json
+X-Token: Bearer {{token}}
+
+{\"id\": \"{{$uuid}}\"}
+### Next
+GET {{login.response.body.$.url}}";
+
+    fn describe(text: &str) -> Vec<(usize, &str, TokenType, u32)> {
+        let document = parse_document(text);
+        let environment = HashMap::from([("token".to_string(), "secret".to_string())]);
+        let lines: Vec<&str> = text.lines().collect();
+
+        tokenize(text, &document, &environment)
+            .into_iter()
+            .map(|token| {
+                (
+                    token.line,
+                    &lines[token.line][token.range],
+                    token.kind,
+                    token.modifiers,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_document() {
+        use TokenType::*;
+
+        assert_eq!(
+            describe(DOCUMENT),
+            vec![
+                (0, "host", Variable, DECLARATION),
+                (0, "example.com", Text, 0),
+                (1, "#", Comment, 0),
+                (1, "@name", Directive, 0),
+                (1, "login", Text, 0),
+                (2, "POST", Method, 0),
+                (2, "https://", Host, 0),
+                (2, "{{host}}", Variable, 0),
+                (2, "/api/users", Text, 0),
+                (2, "page", QueryKey, 0),
+                (2, "2", Text, 0),
+                (2, "q", QueryKey, 0),
+                (2, "{{query}}", Variable, UNRESOLVED),
+                (2, "HTTP/1.1", Method, 0),
+                (3, "Content-Type", HeaderName, 0),
+                (3, "application/json", Text, 0),
+                (4, "X-Token", HeaderName, 0),
+                (4, "Bearer ", Text, 0),
+                (4, "{{token}}", Variable, 0),
+                (6, "{{$uuid}}", Variable, 0),
+                (7, "### Next", Comment, 0),
+                (8, "GET", Method, 0),
+                (8, "{{login.response.body.$.url}}", Variable, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_comments_and_unknown_requests() {
+        use TokenType::*;
+
+        assert_eq!(
+            describe("// just a note\nGET {{missing.response.body}}/x"),
+            vec![
+                (0, "// just a note", Comment, 0),
+                (1, "GET", Method, 0),
+                (1, "{{missing.response.body}}", Variable, UNRESOLVED),
+                (1, "/x", Text, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_uses_relative_utf16_positions() {
+        let text = "GET https://é.com\n\n### x";
+        let document = parse_document(text);
+        let encoded = semantic_tokens(text, &document, &HashMap::new());
+
+        let triples: Vec<(u32, u32, u32)> = encoded
+            .iter()
+            .map(|token| (token.delta_line, token.delta_start, token.length))
+            .collect();
+        assert_eq!(triples, vec![(0, 0, 3), (0, 4, 13), (2, 0, 5)]);
+    }
+}
@@ -0,0 +1,76 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// User settings, given as `initializationOptions` or with `workspace/didChangeConfiguration`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    /// Whether sending several requests stops at the first one that fails
+    pub stop_on_failure: bool,
+    /// Whether every request opens new connections instead of reusing pooled ones, so that
+    /// reported durations include connection setup
+    pub fresh_connections: bool,
+    /// Whether the cookie jar is saved to `.http-cookies.json` in the workspace and restored
+    /// on startup
+    pub persist_cookies: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            stop_on_failure: true,
+            fresh_connections: false,
+            persist_cookies: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads settings given directly or nested under an `http` key. Missing settings keep
+    /// their defaults and unknown ones are ignored.
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let value = value.get("http").unwrap_or(value);
+        if value.is_null() {
+            return Ok(Settings::default());
+        }
+
+        serde_json::from_value(value.clone()).map_err(|e| format!("Invalid settings: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn settings(json: &str) -> Result<Settings, String> {
+        Settings::from_value(&serde_json::from_str(json).expect("Valid JSON"))
+    }
+
+    #[rstest]
+    #[case("null", Settings::default())]
+    #[case("{}", Settings::default())]
+    #[case(
+        r#"{"stopOnFailure": false}"#,
+        Settings { stop_on_failure: false, ..Settings::default() }
+    )]
+    #[case(
+        r#"{"http": {"freshConnections": true}, "other": 1}"#,
+        Settings { fresh_connections: true, ..Settings::default() }
+    )]
+    #[case(
+        r#"{"persistCookies": true}"#,
+        Settings { persist_cookies: true, ..Settings::default() }
+    )]
+    #[case(r#"{"unknown": true}"#, Settings::default())]
+    fn test_from_value(#[case] json: &str, #[case] expected: Settings) {
+        assert_eq!(settings(json), Ok(expected));
+    }
+
+    #[test]
+    fn test_from_value_rejects_invalid_types() {
+        let error = settings(r#"{"stopOnFailure": "yes"}"#).expect_err("Expected invalid settings");
+
+        assert!(error.starts_with("Invalid settings"), "{}", error);
+    }
+}
@@ -0,0 +1,738 @@
+use crate::multipart::{self, PartContent};
+use crate::parser::HttpRequest;
+use crate::variables;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Client libraries a request can be turned into code for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Reqwest,
+    Python,
+    JavaScript,
+    Go,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::Reqwest,
+        Language::Python,
+        Language::JavaScript,
+        Language::Go,
+    ];
+
+    /// Identifier passed as the `http.generateCode` command argument
+    pub fn id(self) -> &'static str {
+        match self {
+            Language::Reqwest => "reqwest",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::Go => "go",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|language| language.id() == id)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Language::Reqwest => "Rust (reqwest)",
+            Language::Python => "Python (requests)",
+            Language::JavaScript => "JavaScript (fetch)",
+            Language::Go => "Go (net/http)",
+        }
+    }
+
+    /// Name of the scratch file the generated code is opened in
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Language::Reqwest => "request.rs",
+            Language::Python => "request.py",
+            Language::JavaScript => "request.mjs",
+            Language::Go => "request.go",
+        }
+    }
+}
+
+/// A request body in the shape client libraries take it, decoded from its Content-Type
+#[derive(Debug, Clone, PartialEq)]
+enum Body {
+    Json(Value),
+    Form(Vec<(String, String)>),
+    Multipart(Vec<FormField>),
+    File(String),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FormField {
+    Text {
+        name: String,
+        value: String,
+    },
+    File {
+        name: String,
+        path: String,
+        content_type: Option<String>,
+    },
+}
+
+/// The request with its placeholders resolved where `variables` has a value
+struct Snippet {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Body>,
+}
+
+impl Snippet {
+    fn uses_files(&self) -> bool {
+        match &self.body {
+            Some(Body::File(_)) => true,
+            Some(Body::Multipart(fields)) => fields
+                .iter()
+                .any(|field| matches!(field, FormField::File { .. })),
+            _ => false,
+        }
+    }
+}
+
+/// Code sending `request` with the given client library. Placeholders `variables` has no
+/// value for are kept as `{{name}}` in the generated strings.
+pub fn generate(
+    request: &HttpRequest,
+    variables: &HashMap<String, String>,
+    language: Language,
+) -> String {
+    let snippet = snippet(request, variables);
+    match language {
+        Language::Reqwest => reqwest(&snippet),
+        Language::Python => python(&snippet),
+        Language::JavaScript => javascript(&snippet),
+        Language::Go => go(&snippet),
+    }
+}
+
+fn snippet(request: &HttpRequest, variables: &HashMap<String, String>) -> Snippet {
+    let resolve = |text: &str| variables::substitute(text, variables);
+    let mime = request
+        .headers
+        .get("content-type")
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let headers = request
+        .headers
+        .iter()
+        // The client library sets the multipart boundary itself
+        .filter(|(name, _)| {
+            request.multipart.is_none() || !name.eq_ignore_ascii_case("content-type")
+        })
+        .map(|(name, value)| (resolve(name), resolve(value)))
+        .collect();
+
+    let body = if let Some(form) = &request.multipart {
+        let fields = form
+            .parts
+            .iter()
+            .filter_map(|part| {
+                let disposition = part.headers.get("content-disposition")?;
+                let name = multipart::header_param(disposition, "name")?.to_string();
+                Some(match &part.content {
+                    PartContent::Text(value) => FormField::Text {
+                        name,
+                        value: resolve(value),
+                    },
+                    PartContent::File(file) => FormField::File {
+                        name,
+                        path: file.path.display().to_string(),
+                        content_type: part.headers.get("content-type").cloned(),
+                    },
+                })
+            })
+            .collect();
+        Some(Body::Multipart(fields))
+    } else if let Some(file) = &request.body_file {
+        Some(Body::File(file.path.display().to_string()))
+    } else {
+        request.body.as_deref().map(|body| {
+            let body = resolve(body);
+            if mime == "application/json" || mime.ends_with("+json") {
+                if let Ok(json) = serde_json::from_str(&body) {
+                    return Body::Json(json);
+                }
+            }
+            if mime == "application/x-www-form-urlencoded" {
+                return Body::Form(
+                    url::form_urlencoded::parse(body.trim().as_bytes())
+                        .into_owned()
+                        .collect(),
+                );
+            }
+            Body::Text(body)
+        })
+    };
+
+    Snippet {
+        method: request.method.clone(),
+        url: resolve(&request.url),
+        headers,
+        body,
+    }
+}
+
+/// A double-quoted string literal, escaped as JSON, which Python, JavaScript and Go all accept
+fn string(text: &str) -> String {
+    serde_json::to_string(text).expect("Failed to serialize string")
+}
+
+/// Pretty-printed JSON with every line after the first indented by `indent`
+fn indented_json(json: &Value, indent: &str) -> String {
+    serde_json::to_string_pretty(json)
+        .expect("Failed to serialize JSON")
+        .replace('\n', &format!("\n{}", indent))
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn reqwest(snippet: &Snippet) -> String {
+    let mut code = String::new();
+
+    if let Some(Body::Multipart(fields)) = &snippet.body {
+        code.push_str("let form = reqwest::multipart::Form::new()");
+        for field in fields {
+            match field {
+                FormField::Text { name, value } => {
+                    code.push_str(&format!("\n    .text({:?}, {:?})", name, value))
+                }
+                FormField::File { name, path, .. } => {
+                    code.push_str(&format!("\n    .file({:?}, {:?})\n    .await?", name, path))
+                }
+            }
+        }
+        code.push_str(";\n\n");
+    }
+
+    let method = match snippet.method.as_str() {
+        "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" => {
+            format!(".{}({:?})", snippet.method.to_lowercase(), snippet.url)
+        }
+        method => format!(".request(reqwest::Method::{}, {:?})", method, snippet.url),
+    };
+    let mut calls = vec![method];
+    for (name, value) in &snippet.headers {
+        calls.push(format!(".header({:?}, {:?})", name, value));
+    }
+    match &snippet.body {
+        Some(Body::Json(json)) => calls.push(format!(
+            ".json(&serde_json::json!({}))",
+            indented_json(json, "    ")
+        )),
+        Some(Body::Form(pairs)) => {
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(name, value)| format!("({:?}, {:?})", name, value))
+                .collect();
+            calls.push(format!(".form(&[{}])", pairs.join(", ")));
+        }
+        Some(Body::Multipart(_)) => calls.push(".multipart(form)".to_string()),
+        Some(Body::File(path)) => calls.push(format!(".body(std::fs::read({:?})?)", path)),
+        Some(Body::Text(text)) => calls.push(format!(".body({:?})", text)),
+        None => {}
+    }
+    calls.push(".send()".to_string());
+    calls.push(".await?;".to_string());
+
+    code.push_str("let client = reqwest::Client::new();\nlet response = client");
+    for call in calls {
+        code.push_str(&format!("\n    {}", call));
+    }
+    code
+}
+
+fn python(snippet: &Snippet) -> String {
+    let dict = |entries: Vec<String>| {
+        let entries: String = entries
+            .iter()
+            .map(|entry| format!("        {},\n", entry))
+            .collect();
+        format!("{{\n{}    }}", entries)
+    };
+
+    let mut args = vec![string(&snippet.url)];
+    if !snippet.headers.is_empty() {
+        // A list keeps repeated headers, which a dict would collapse
+        let headers: String = snippet
+            .headers
+            .iter()
+            .map(|(name, value)| format!("        ({}, {}),\n", string(name), string(value)))
+            .collect();
+        args.push(format!("headers=[\n{}    ]", headers));
+    }
+    match &snippet.body {
+        Some(Body::Json(json)) => args.push(format!("json={}", python_value(json, 1))),
+        Some(Body::Form(pairs)) => {
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(name, value)| format!("({}, {})", string(name), string(value)))
+                .collect();
+            args.push(format!("data=[{}]", pairs.join(", ")));
+        }
+        Some(Body::Multipart(fields)) => {
+            let mut data = Vec::new();
+            let mut files = Vec::new();
+            for field in fields {
+                match field {
+                    FormField::Text { name, value } => {
+                        data.push(format!("{}: {}", string(name), string(value)))
+                    }
+                    FormField::File {
+                        name,
+                        path,
+                        content_type,
+                    } => {
+                        let mut file = format!(
+                            "{}, open({}, \"rb\")",
+                            string(&file_name(path)),
+                            string(path)
+                        );
+                        if let Some(content_type) = content_type {
+                            file.push_str(&format!(", {}", string(content_type)));
+                        }
+                        files.push(format!("{}: ({})", string(name), file));
+                    }
+                }
+            }
+            if !data.is_empty() {
+                args.push(format!("data={}", dict(data)));
+            }
+            if !files.is_empty() {
+                args.push(format!("files={}", dict(files)));
+            }
+        }
+        Some(Body::File(path)) => args.push(format!("data=open({}, \"rb\")", string(path))),
+        Some(Body::Text(text)) => args.push(format!("data={}", string(text))),
+        None => {}
+    }
+
+    let method = snippet.method.to_lowercase();
+    let call = match args.as_slice() {
+        [url] => format!("requests.{}({})", method, url),
+        _ => {
+            let args: String = args.iter().map(|arg| format!("    {},\n", arg)).collect();
+            format!("requests.{}(\n{})", method, args)
+        }
+    };
+    format!("import requests\n\nresponse = {}", call)
+}
+
+/// A Python literal for a JSON value, with nested lines indented for `depth`
+fn python_value(value: &Value, depth: usize) -> String {
+    let indent = "    ".repeat(depth);
+    let items = |items: Vec<String>, open: &str, close: &str| {
+        if items.is_empty() {
+            return format!("{}{}", open, close);
+        }
+        let items: String = items
+            .iter()
+            .map(|item| format!("{}    {},\n", indent, item))
+            .collect();
+        format!("{}\n{}{}{}", open, items, indent, close)
+    };
+
+    match value {
+        Value::Null => "None".to_string(),
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => string(text),
+        Value::Array(array) => items(
+            array
+                .iter()
+                .map(|item| python_value(item, depth + 1))
+                .collect(),
+            "[",
+            "]",
+        ),
+        Value::Object(object) => items(
+            object
+                .iter()
+                .map(|(key, item)| format!("{}: {}", string(key), python_value(item, depth + 1)))
+                .collect(),
+            "{",
+            "}",
+        ),
+    }
+}
+
+fn javascript(snippet: &Snippet) -> String {
+    let mut code = String::new();
+    if snippet.uses_files() {
+        code.push_str("import { readFile } from \"node:fs/promises\";\n\n");
+    }
+
+    if let Some(Body::Multipart(fields)) = &snippet.body {
+        code.push_str("const form = new FormData();\n");
+        for field in fields {
+            match field {
+                FormField::Text { name, value } => code.push_str(&format!(
+                    "form.append({}, {});\n",
+                    string(name),
+                    string(value)
+                )),
+                FormField::File {
+                    name,
+                    path,
+                    content_type,
+                } => {
+                    let options = content_type
+                        .as_ref()
+                        .map(|content_type| format!(", {{ type: {} }}", string(content_type)))
+                        .unwrap_or_default();
+                    code.push_str(&format!(
+                        "form.append({}, new Blob([await readFile({})]{}), {});\n",
+                        string(name),
+                        string(path),
+                        options,
+                        string(&file_name(path))
+                    ));
+                }
+            }
+        }
+        code.push('\n');
+    }
+
+    // Headers keeps repeated headers, which an object literal would collapse
+    if !snippet.headers.is_empty() {
+        code.push_str("const headers = new Headers();\n");
+        for (name, value) in &snippet.headers {
+            code.push_str(&format!(
+                "headers.append({}, {});\n",
+                string(name),
+                string(value)
+            ));
+        }
+        code.push('\n');
+    }
+
+    let mut options = vec![format!("method: {}", string(&snippet.method))];
+    if !snippet.headers.is_empty() {
+        options.push("headers".to_string());
+    }
+    match &snippet.body {
+        Some(Body::Json(json)) => options.push(format!(
+            "body: JSON.stringify({})",
+            indented_json(json, "  ")
+        )),
+        Some(Body::Form(pairs)) => {
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(name, value)| format!("[{}, {}]", string(name), string(value)))
+                .collect();
+            options.push(format!("body: new URLSearchParams([{}])", pairs.join(", ")));
+        }
+        Some(Body::Multipart(_)) => options.push("body: form".to_string()),
+        Some(Body::File(path)) => options.push(format!("body: await readFile({})", string(path))),
+        Some(Body::Text(text)) => options.push(format!("body: {}", string(text))),
+        None => {}
+    }
+
+    let options: String = options
+        .iter()
+        .map(|option| format!("  {},\n", option))
+        .collect();
+    code.push_str(&format!(
+        "const response = await fetch({}, {{\n{}}});",
+        string(&snippet.url),
+        options
+    ));
+    code
+}
+
+fn go(snippet: &Snippet) -> String {
+    const CHECK: &str = "if err != nil {\n\treturn err\n}\n";
+    // Raw strings keep multi-line bodies readable, but can't contain a backtick
+    let literal = |text: &str| {
+        if text.contains('`') {
+            string(text)
+        } else {
+            format!("`{}`", text)
+        }
+    };
+
+    let mut code = String::new();
+    let body = match &snippet.body {
+        Some(Body::Json(json)) => {
+            let json = serde_json::to_string_pretty(json).expect("Failed to serialize JSON");
+            code.push_str(&format!("body := strings.NewReader({})\n", literal(&json)));
+            "body"
+        }
+        Some(Body::Form(pairs)) => {
+            let encoded = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish();
+            code.push_str(&format!(
+                "body := strings.NewReader({})\n",
+                string(&encoded)
+            ));
+            "body"
+        }
+        Some(Body::Text(text)) => {
+            code.push_str(&format!("body := strings.NewReader({})\n", literal(text)));
+            "body"
+        }
+        Some(Body::File(path)) => {
+            code.push_str(&format!(
+                "body, err := os.Open({})\n{}",
+                string(path),
+                CHECK
+            ));
+            code.push_str("defer body.Close()\n");
+            "body"
+        }
+        Some(Body::Multipart(fields)) => {
+            code.push_str("body := &bytes.Buffer{}\nform := multipart.NewWriter(body)\n");
+            for field in fields {
+                match field {
+                    FormField::Text { name, value } => code.push_str(&format!(
+                        "if err := form.WriteField({}, {}); err != nil {{\n\treturn err\n}}\n",
+                        string(name),
+                        string(value)
+                    )),
+                    FormField::File { name, path, .. } => {
+                        let file = format!(
+                            "file, err := os.Open({})\n{}defer file.Close()\n\
+                             part, err := form.CreateFormFile({}, {})\n{}\
+                             if _, err := io.Copy(part, file); err != nil {{\n\treturn err\n}}\n",
+                            string(path),
+                            CHECK,
+                            string(name),
+                            string(&file_name(path)),
+                            CHECK
+                        );
+                        let file: String =
+                            file.lines().map(|line| format!("\t{}\n", line)).collect();
+                        code.push_str(&format!("{{\n{}}}\n", file));
+                    }
+                }
+            }
+            code.push_str("if err := form.Close(); err != nil {\n\treturn err\n}\n");
+            "body"
+        }
+        None => "nil",
+    };
+    if !code.is_empty() {
+        code.push('\n');
+    }
+
+    let method = snippet.method[..1].to_string() + &snippet.method[1..].to_lowercase();
+    code.push_str(&format!(
+        "req, err := http.NewRequest(http.Method{}, {}, {})\n{}",
+        method,
+        string(&snippet.url),
+        body,
+        CHECK
+    ));
+    for (name, value) in &snippet.headers {
+        code.push_str(&format!(
+            "req.Header.Add({}, {})\n",
+            string(name),
+            string(value)
+        ));
+    }
+    if matches!(snippet.body, Some(Body::Multipart(_))) {
+        code.push_str("req.Header.Set(\"Content-Type\", form.FormDataContentType())\n");
+    }
+
+    code.push_str(&format!(
+        "\nresp, err := http.DefaultClient.Do(req)\n{}defer resp.Body.Close()",
+        CHECK
+    ));
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_document;
+    use rstest::rstest;
+
+    const JSON_REQUEST: &str = "POST https://{{host}}/users\nContent-Type: application/json\n\
+                                Authorization: Bearer {{token}}\n\n\
+                                {\"name\": \"Ada\", \"admin\": false, \"tags\": [\"a\"], \"boss\": null}";
+
+    fn generate_for(text: &str, language: Language) -> String {
+        let request = parse_document(text)
+            .requests
+            .into_iter()
+            .next()
+            .expect("Expected a request");
+        let variables = HashMap::from([("host".to_string(), "example.com".to_string())]);
+        generate(&request, &variables, language)
+    }
+
+    #[test]
+    fn test_reqwest() {
+        assert_eq!(
+            generate_for(JSON_REQUEST, Language::Reqwest),
+            r#"let client = reqwest::Client::new();
+let response = client
+    .post("https://example.com/users")
+    .header("Content-Type", "application/json")
+    .header("Authorization", "Bearer {{token}}")
+    .json(&serde_json::json!({
+      "name": "Ada",
+      "admin": false,
+      "tags": [
+        "a"
+      ],
+      "boss": null
+    }))
+    .send()
+    .await?;"#
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        assert_eq!(
+            generate_for(JSON_REQUEST, Language::Python),
+            r#"import requests
+
+response = requests.post(
+    "https://example.com/users",
+    headers=[
+        ("Content-Type", "application/json"),
+        ("Authorization", "Bearer {{token}}"),
+    ],
+    json={
+        "name": "Ada",
+        "admin": False,
+        "tags": [
+            "a",
+        ],
+        "boss": None,
+    },
+)"#
+        );
+    }
+
+    #[test]
+    fn test_javascript() {
+        assert_eq!(
+            generate_for(JSON_REQUEST, Language::JavaScript),
+            r#"const headers = new Headers();
+headers.append("Content-Type", "application/json");
+headers.append("Authorization", "Bearer {{token}}");
+
+const response = await fetch("https://example.com/users", {
+  method: "POST",
+  headers,
+  body: JSON.stringify({
+    "name": "Ada",
+    "admin": false,
+    "tags": [
+      "a"
+    ],
+    "boss": null
+  }),
+});"#
+        );
+    }
+
+    #[test]
+    fn test_go() {
+        assert_eq!(
+            generate_for("GET https://{{host}}/users\nAccept: */*", Language::Go),
+            "req, err := http.NewRequest(http.MethodGet, \"https://example.com/users\", nil)\n\
+             if err != nil {\n\treturn err\n}\n\
+             req.Header.Add(\"Accept\", \"*/*\")\n\n\
+             resp, err := http.DefaultClient.Do(req)\n\
+             if err != nil {\n\treturn err\n}\n\
+             defer resp.Body.Close()"
+        );
+    }
+
+    #[rstest]
+    #[case(
+        Language::Reqwest,
+        r#".form(&[("name", "Ada Lovelace"), ("tag", "a&b")])"#
+    )]
+    #[case(Language::Python, r#"data=[("name", "Ada Lovelace"), ("tag", "a&b")]"#)]
+    #[case(
+        Language::JavaScript,
+        r#"body: new URLSearchParams([["name", "Ada Lovelace"], ["tag", "a&b"]])"#
+    )]
+    #[case(
+        Language::Go,
+        r#"body := strings.NewReader("name=Ada+Lovelace&tag=a%26b")"#
+    )]
+    fn test_form_body_is_decoded(#[case] language: Language, #[case] expected: &str) {
+        let text = "POST https://example.com\nContent-Type: application/x-www-form-urlencoded\n\n\
+                    name=Ada+Lovelace&tag=a%26b";
+        let code = generate_for(text, language);
+
+        assert!(code.contains(expected), "unexpected code:\n{}", code);
+    }
+
+    #[rstest]
+    #[case(Language::Reqwest, &[".text(\"title\", \"Beach\")", ".file(\"photo\", \"./a.png\")", ".multipart(form)"])]
+    #[case(Language::Python, &["\"title\": \"Beach\"", "\"photo\": (\"a.png\", open(\"./a.png\", \"rb\"), \"image/png\")"])]
+    #[case(Language::JavaScript, &["import { readFile }", "new Blob([await readFile(\"./a.png\")], { type: \"image/png\" }), \"a.png\")", "body: form"])]
+    #[case(Language::Go, &["form.WriteField(\"title\", \"Beach\")", "form.CreateFormFile(\"photo\", \"a.png\")", "form.FormDataContentType()"])]
+    fn test_multipart_body(#[case] language: Language, #[case] expected: &[&str]) {
+        let text =
+            "POST https://example.com/upload\nContent-Type: multipart/form-data; boundary=b\n\n\
+                    --b\nContent-Disposition: form-data; name=\"title\"\n\nBeach\n\
+                    --b\nContent-Disposition: form-data; name=\"photo\"; filename=\"a.png\"\n\
+                    Content-Type: image/png\n\n< ./a.png\n--b--";
+        let code = generate_for(text, language);
+
+        for expected in expected {
+            assert!(code.contains(expected), "unexpected code:\n{}", code);
+        }
+        assert!(!code.contains("boundary=b"), "unexpected code:\n{}", code);
+    }
+
+    #[rstest]
+    #[case(Language::Reqwest, &[".header(\"Accept\", \"text/html\")", ".header(\"Accept\", \"*/*\")"])]
+    #[case(Language::Python, &["(\"Accept\", \"text/html\"),", "(\"Accept\", \"*/*\"),"])]
+    #[case(Language::JavaScript, &["headers.append(\"Accept\", \"text/html\");", "headers.append(\"Accept\", \"*/*\");"])]
+    #[case(Language::Go, &["req.Header.Add(\"Accept\", \"text/html\")", "req.Header.Add(\"Accept\", \"*/*\")"])]
+    fn test_repeated_headers_are_kept(#[case] language: Language, #[case] expected: &[&str]) {
+        let text = "GET https://example.com\nAccept: text/html\nAccept: */*";
+        let code = generate_for(text, language);
+
+        for expected in expected {
+            assert!(code.contains(expected), "unexpected code:\n{}", code);
+        }
+    }
+
+    #[rstest]
+    #[case(Language::Reqwest, ".body(\"it's `raw`\\n{{id}}\")")]
+    #[case(Language::Python, "data=\"it's `raw`\\n{{id}}\"")]
+    #[case(Language::JavaScript, "body: \"it's `raw`\\n{{id}}\"")]
+    #[case(Language::Go, "strings.NewReader(\"it's `raw`\\n{{id}}\")")]
+    fn test_text_body_is_escaped(#[case] language: Language, #[case] expected: &str) {
+        let text = "PUT https://example.com\nContent-Type: text/plain\n\nit's `raw`\n{{id}}";
+        let code = generate_for(text, language);
+
+        assert!(code.contains(expected), "unexpected code:\n{}", code);
+    }
+
+    #[test]
+    fn test_language_ids_round_trip() {
+        for language in Language::ALL {
+            assert_eq!(Language::from_id(language.id()), Some(language));
+        }
+        assert_eq!(Language::from_id("cobol"), None);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::parser::{self, HttpRequest, ParsedDocument};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tower_lsp::lsp_types::{
+    DocumentSymbol, Location, Position, Range, SymbolInformation, SymbolKind, Url,
+};
+
+/// Directories that never contain .http files worth indexing
+const SKIPPED_DIRS: [&str; 2] = ["node_modules", "target"];
+
+/// One outline entry per request, labelled by its `###` title or `# @name`
+pub fn document_symbols(document: &ParsedDocument) -> Vec<DocumentSymbol> {
+    document
+        .requests
+        .iter()
+        .map(|request| {
+            let request_line = format!("{} {}", request.method, request.url);
+            let (name, detail) = match request.title.as_ref().or(request.name.as_ref()) {
+                Some(label) => (label.clone(), Some(request_line)),
+                None => (request_line, None),
+            };
+
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name,
+                detail,
+                kind: SymbolKind::METHOD,
+                tags: None,
+                deprecated: None,
+                range: Range::new(
+                    Position::new(request.block_lines.start as u32, 0),
+                    Position::new(request.block_lines.end as u32, 0),
+                ),
+                selection_range: line_range(request),
+                children: None,
+            }
+        })
+        .collect()
+}
+
+/// Named requests whose name contains `query`, ignoring case
+pub fn named_request_symbols(
+    uri: &Url,
+    requests: &[HttpRequest],
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+
+    requests
+        .iter()
+        .filter_map(|request| {
+            let name = request.name.as_ref()?;
+            if !name.to_lowercase().contains(&query) {
+                return None;
+            }
+
+            #[allow(deprecated)]
+            Some(SymbolInformation {
+                name: name.clone(),
+                kind: SymbolKind::METHOD,
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri.clone(), line_range(request)),
+                container_name: Some(format!("{} {}", request.method, request.url)),
+            })
+        })
+        .collect()
+}
+
+/// Requests of the .http files in a workspace, parsed again only when a file's modification
+/// time changes
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    files: HashMap<PathBuf, (Option<SystemTime>, Vec<HttpRequest>)>,
+}
+
+impl WorkspaceIndex {
+    /// Named requests matching `query` in the .http files under `root`, other than the files
+    /// in `skipped`. This reads the file system, so it belongs off the async runtime.
+    pub fn named_request_symbols(
+        &mut self,
+        root: &Path,
+        skipped: &[Url],
+        query: &str,
+    ) -> Vec<SymbolInformation> {
+        let paths = find_http_files(root);
+        self.files.retain(|path, _| paths.contains(path));
+
+        let mut symbols = Vec::new();
+        for path in paths {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            if skipped.contains(&uri) {
+                continue;
+            }
+
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            let indexed = self.files.get(&path).map(|(indexed, _)| *indexed);
+            if modified.is_none() || indexed != Some(modified) {
+                let Ok(content) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+                let requests = parser::parse_http_file(&content);
+                self.files.insert(path.clone(), (modified, requests));
+            }
+            let (_, requests) = &self.files[&path];
+            symbols.extend(named_request_symbols(&uri, requests, query));
+        }
+
+        symbols
+    }
+}
+
+/// Recursively finds the .http files under `root`, skipping hidden and build directories
+pub fn find_http_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+
+            if path.is_dir() {
+                if !file_name.starts_with('.') && !SKIPPED_DIRS.contains(&file_name.as_ref()) {
+                    dirs.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "http") {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+fn line_range(request: &HttpRequest) -> Range {
+    let line = request.line_number as u32;
+    Range::new(Position::new(line, 0), Position::new(line, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_document;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = r#"# @name login
+POST https://example.com/login
+### Create user
+# @name createUser
+POST https://example.com/users
+###
+GET https://example.com/health"#;
+
+    #[test]
+    fn test_document_symbols_use_title_then_name() {
+        let symbols = document_symbols(&parse_document(DOCUMENT));
+        let entries: Vec<(&str, Option<&str>, u32, u32)> = symbols
+            .iter()
+            .map(|s| {
+                (
+                    s.name.as_str(),
+                    s.detail.as_deref(),
+                    s.range.start.line,
+                    s.selection_range.start.line,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                ("login", Some("POST https://example.com/login"), 0, 1),
+                ("Create user", Some("POST https://example.com/users"), 2, 4),
+                ("GET https://example.com/health", None, 5, 6),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("", vec!["login", "createUser"])]
+    #[case("USER", vec!["createUser"])]
+    #[case("missing", vec![])]
+    fn test_named_request_symbols(#[case] query: &str, #[case] expected: Vec<&str>) {
+        let uri = Url::parse("file:///project/api.http").expect("Valid URI");
+        let symbols = named_request_symbols(&uri, &parse_document(DOCUMENT).requests, query);
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+
+        assert_eq!(names, expected);
+        assert!(symbols.iter().all(|s| s.location.uri == uri));
+    }
+
+    #[test]
+    fn test_find_http_files_skips_hidden_and_build_dirs() {
+        let root = std::env::temp_dir().join(format!("http-lsp-symbols-{}", std::process::id()));
+        for dir in ["api/users", ".git", "target", "node_modules"] {
+            std::fs::create_dir_all(root.join(dir)).expect("Failed to create temp dir");
+        }
+        for file in [
+            "root.http",
+            "api/users/users.http",
+            "api/notes.txt",
+            ".git/ignored.http",
+            "target/ignored.http",
+            "node_modules/ignored.http",
+        ] {
+            std::fs::write(root.join(file), "").expect("Failed to write file");
+        }
+
+        let files = find_http_files(&root);
+        std::fs::remove_dir_all(&root).expect("Failed to remove temp dir");
+
+        assert_eq!(
+            files,
+            vec![root.join("api/users/users.http"), root.join("root.http")]
+        );
+    }
+
+    #[test]
+    fn test_workspace_index_reparses_modified_files_only() {
+        let root = std::env::temp_dir().join(format!("http-lsp-index-{}", std::process::id()));
+        std::fs::create_dir_all(&root).expect("Failed to create temp dir");
+        let path = root.join("api.http");
+        let write = |content: &str, modified: SystemTime| {
+            std::fs::write(&path, content).expect("Failed to write file");
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(modified))
+                .expect("Failed to set modification time");
+        };
+        let names = |index: &mut WorkspaceIndex, skipped: &[Url]| -> Vec<String> {
+            index
+                .named_request_symbols(&root, skipped, "")
+                .into_iter()
+                .map(|symbol| symbol.name)
+                .collect()
+        };
+        let earlier = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        let later = earlier + std::time::Duration::from_secs(60);
+        let mut index = WorkspaceIndex::default();
+
+        write("# @name first\nGET https://example.com", earlier);
+        assert_eq!(names(&mut index, &[]), vec!["first"]);
+
+        write("# @name second\nGET https://example.com", earlier);
+        assert_eq!(names(&mut index, &[]), vec!["first"]);
+
+        write("# @name second\nGET https://example.com", later);
+        assert_eq!(names(&mut index, &[]), vec!["second"]);
+
+        let uri = Url::from_file_path(&path).expect("Valid file URI");
+        assert!(names(&mut index, &[uri]).is_empty());
+
+        std::fs::remove_dir_all(&root).expect("Failed to remove temp dir");
+        assert!(names(&mut index, &[]).is_empty());
+        assert!(index.files.is_empty());
+    }
+}
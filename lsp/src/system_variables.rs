@@ -0,0 +1,219 @@
+use std::path::Path;
+
+/// File read by `{{$dotenv NAME}}`, looked up next to the .http file
+pub const DOTENV_FILE: &str = ".env";
+
+/// Every system variable with its arguments and a short description
+pub const SYSTEM_VARIABLES: [(&str, &str); 7] = [
+    ("$uuid", "A random UUID v4"),
+    ("$guid", "A random UUID v4, alias of $uuid"),
+    ("$timestamp", "Current UTC time as Unix seconds"),
+    ("$isoTimestamp", "Current UTC time in ISO 8601 format"),
+    (
+        "$randomInt min max",
+        "A random integer from min (inclusive) to max (exclusive)",
+    ),
+    (
+        "$processEnv NAME",
+        "Value of the LSP process's environment variable NAME",
+    ),
+    (
+        "$dotenv NAME",
+        "Value of NAME in the .env file next to the .http file",
+    ),
+];
+
+/// Evaluates a system variable expression such as `$uuid` or `$randomInt 1 100`.
+/// Returns `None` when the expression is not a system variable, so other sources can be tried.
+pub fn evaluate(expression: &str, document_dir: Option<&Path>) -> Option<Result<String, String>> {
+    let mut parts = expression.split_whitespace();
+    let name = parts.next()?.strip_prefix('$')?;
+    let args: Vec<&str> = parts.collect();
+
+    let result = match name {
+        "uuid" | "guid" => Ok(uuid::Uuid::new_v4().to_string()),
+        "timestamp" => Ok(chrono::Utc::now().timestamp().to_string()),
+        "isoTimestamp" => {
+            Ok(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        }
+        "randomInt" => random_int(&args),
+        "processEnv" => process_env(&args),
+        "dotenv" => dotenv(&args, document_dir),
+        _ => Err(format!("Unknown system variable: ${}", name)),
+    };
+
+    Some(result)
+}
+
+/// Returns a random integer in `min..max`, matching REST Client's exclusive upper bound
+fn random_int(args: &[&str]) -> Result<String, String> {
+    let [min, max] = args else {
+        return Err("$randomInt expects two arguments: min max".to_string());
+    };
+
+    let min: i64 = min
+        .parse()
+        .map_err(|_| format!("$randomInt: invalid minimum '{}'", min))?;
+    let max: i64 = max
+        .parse()
+        .map_err(|_| format!("$randomInt: invalid maximum '{}'", max))?;
+
+    if min >= max {
+        return Err(format!(
+            "$randomInt: minimum {} must be less than maximum {}",
+            min, max
+        ));
+    }
+
+    Ok(rand::random_range(min..max).to_string())
+}
+
+fn process_env(args: &[&str]) -> Result<String, String> {
+    let [name] = args else {
+        return Err("$processEnv expects one argument: NAME".to_string());
+    };
+
+    std::env::var(name).map_err(|_| format!("$processEnv: {} is not set", name))
+}
+
+fn dotenv(args: &[&str], document_dir: Option<&Path>) -> Result<String, String> {
+    let [name] = args else {
+        return Err("$dotenv expects one argument: NAME".to_string());
+    };
+
+    let dir = document_dir.ok_or("$dotenv: the .http file has no directory")?;
+    let path = dir.join(DOTENV_FILE);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("$dotenv: failed to read {}: {}", path.display(), e))?;
+
+    lookup_dotenv(&content, name)
+        .ok_or_else(|| format!("$dotenv: {} is not defined in {}", name, path.display()))
+}
+
+/// Finds `NAME=value` in dotenv content, ignoring comments and stripping surrounding quotes
+fn lookup_dotenv(content: &str, name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        if line.starts_with('#') {
+            return None;
+        }
+
+        let (key, value) = line.split_once('=')?;
+        if key.trim() != name {
+            return None;
+        }
+
+        let value = value.trim();
+        let unquoted = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+            .unwrap_or(value);
+        Some(unquoted.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn evaluate_ok(expression: &str) -> String {
+        evaluate(expression, None)
+            .expect("Expected a system variable")
+            .expect("Expected evaluation to succeed")
+    }
+
+    #[rstest]
+    #[case("baseUrl")]
+    #[case("login.response.body.$.token")]
+    #[case("")]
+    fn test_non_system_variables_are_ignored(#[case] expression: &str) {
+        assert!(evaluate(expression, None).is_none());
+    }
+
+    #[test]
+    fn test_uuid_is_fresh_v4() {
+        let first = evaluate_ok("$uuid");
+        let second = evaluate_ok("$uuid");
+
+        let parsed = uuid::Uuid::parse_str(&first).expect("Expected a valid UUID");
+        assert_eq!(parsed.get_version_num(), 4);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_timestamp_is_unix_seconds() {
+        let timestamp: i64 = evaluate_ok("$timestamp")
+            .parse()
+            .expect("Expected an integer timestamp");
+        let now = chrono::Utc::now().timestamp();
+
+        assert!((now - timestamp).abs() <= 1);
+    }
+
+    #[test]
+    fn test_iso_timestamp_is_rfc3339() {
+        let value = evaluate_ok("$isoTimestamp");
+
+        assert!(chrono::DateTime::parse_from_rfc3339(&value).is_ok());
+        assert!(value.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_random_int_stays_in_range() {
+        for _ in 0..100 {
+            let value: i64 = evaluate_ok("$randomInt 5 8")
+                .parse()
+                .expect("Expected an integer");
+            assert!((5..8).contains(&value));
+        }
+    }
+
+    #[rstest]
+    #[case("$randomInt")]
+    #[case("$randomInt 10")]
+    #[case("$randomInt a 10")]
+    #[case("$randomInt 10 10")]
+    #[case("$processEnv")]
+    #[case("$processEnv HTTP_LSP_SURELY_UNSET_VARIABLE")]
+    #[case("$dotenv TOKEN")]
+    #[case("$unknown")]
+    fn test_invalid_expressions_are_errors(#[case] expression: &str) {
+        let result = evaluate(expression, None).expect("Expected a system variable");
+        assert!(result.is_err(), "Expected '{}' to fail", expression);
+    }
+
+    #[test]
+    fn test_process_env_reads_environment() {
+        let path = std::env::var("PATH").expect("PATH should be set");
+        assert_eq!(evaluate_ok("$processEnv PATH"), path);
+    }
+
+    #[rstest]
+    #[case("TOKEN=abc", "TOKEN", Some("abc"))]
+    #[case("# TOKEN=commented\nTOKEN = spaced ", "TOKEN", Some("spaced"))]
+    #[case("TOKEN=\"quoted value\"", "TOKEN", Some("quoted value"))]
+    #[case("TOKEN='single'", "TOKEN", Some("single"))]
+    #[case("export TOKEN=exported", "TOKEN", Some("exported"))]
+    #[case("OTHER=1", "TOKEN", None)]
+    fn test_lookup_dotenv(
+        #[case] content: &str,
+        #[case] name: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(lookup_dotenv(content, name).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_dotenv_reads_file_next_to_document() {
+        let dir = std::env::temp_dir().join(format!("http-lsp-dotenv-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+        std::fs::write(dir.join(DOTENV_FILE), "API_KEY=secret\n").expect("Failed to write .env");
+
+        let result = evaluate("$dotenv API_KEY", Some(&dir)).expect("Expected a system variable");
+        std::fs::remove_dir_all(&dir).expect("Failed to remove temp dir");
+
+        assert_eq!(result, Ok("secret".to_string()));
+    }
+}
@@ -0,0 +1,444 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ActiveKeyExchange, CompletedKeyExchange, CryptoProvider, SupportedKxGroup};
+use rustls::ffdhe_groups::FfdheGroup;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, NamedGroup, ProtocolVersion, SignatureScheme};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Columns of the longest bar in a waterfall
+const WATERFALL_WIDTH: usize = 40;
+
+/// Time spent in each phase of an exchange. Connection phases are `None` when a pooled
+/// connection was reused, or when they don't apply, such as DNS for an IP address or TLS
+/// over plain HTTP.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    /// From the connection being ready to the response headers arriving
+    pub first_byte: Duration,
+    /// Reading the response body
+    pub download: Duration,
+}
+
+impl Timings {
+    /// One line per phase with a bar placed after the phases before it, then the total
+    pub fn waterfall(&self) -> String {
+        let phases = [
+            ("DNS lookup", self.dns),
+            ("TCP connect", self.connect),
+            ("TLS handshake", self.tls),
+            ("Waiting (TTFB)", Some(self.first_byte)),
+            ("Download", Some(self.download)),
+        ];
+        let total: Duration = phases.iter().filter_map(|(_, duration)| *duration).sum();
+
+        let mut lines = Vec::new();
+        if self.connect.is_none() {
+            lines.push(format!("{:<16}{:>10}", "Connection", "reused"));
+        }
+        let mut offset = Duration::ZERO;
+        for (label, duration) in phases {
+            let Some(duration) = duration else {
+                continue;
+            };
+            lines.push(format!(
+                "{:<16}{:>10}  {}",
+                label,
+                millis(duration),
+                bar(offset, duration, total)
+            ));
+            offset += duration;
+        }
+        lines.push(format!("{:<16}{:>10}", "Total", millis(total)));
+
+        lines.join("\n")
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// A bar starting `offset` into `total`, at least one column wide
+fn bar(offset: Duration, duration: Duration, total: Duration) -> String {
+    let column = |at: Duration| {
+        if total.is_zero() {
+            return 0;
+        }
+        (at.as_secs_f64() / total.as_secs_f64() * WATERFALL_WIDTH as f64).round() as usize
+    };
+    let start = column(offset).min(WATERFALL_WIDTH - 1);
+    let end = column(offset + duration).clamp(start + 1, WATERFALL_WIDTH);
+
+    format!("{}{}", " ".repeat(start), "█".repeat(end - start))
+}
+
+/// Instants marked while a connection is set up for the request being timed
+#[derive(Debug, Default)]
+struct Marks {
+    dns: Option<(Instant, Instant)>,
+    /// From asking for a connection, name lookup included, until it is ready
+    connect: Option<(Instant, Instant)>,
+    tls_start: Option<Instant>,
+}
+
+tokio::task_local! {
+    static MARKS: Arc<Mutex<Marks>>;
+}
+
+/// Updates the marks of the request being sent by the current task. Connections set up
+/// elsewhere, such as those hyper finishes in the background, aren't timed.
+fn mark(update: impl FnOnce(&mut Marks)) {
+    let _ = MARKS.try_with(|marks| update(&mut marks.lock().unwrap_or_else(|e| e.into_inner())));
+}
+
+/// Collects the phases of sending one request. Only the clients built with the hooks
+/// below report connection phases.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    marks: Arc<Mutex<Marks>>,
+}
+
+impl Recorder {
+    /// Runs `sending`, noting when the connection it sets up, if any, passes each phase
+    pub async fn record<F: Future>(&self, sending: F) -> F::Output {
+        MARKS.scope(Arc::clone(&self.marks), sending).await
+    }
+
+    /// The phases of a request sent at `sent`, whose headers arrived at `headers` and whose
+    /// body was read by `done`. With redirects, connection phases are those of the last
+    /// connection set up.
+    pub fn timings(&self, sent: Instant, headers: Instant, done: Instant) -> Timings {
+        let marks = self.marks.lock().unwrap_or_else(|e| e.into_inner());
+        let mut timings = Timings {
+            first_byte: headers.saturating_duration_since(sent),
+            download: done.saturating_duration_since(headers),
+            ..Timings::default()
+        };
+        let Some((connect_start, connected)) = marks.connect else {
+            return timings;
+        };
+
+        let dns = marks.dns.filter(|(start, _)| *start >= connect_start);
+        let tcp_start = dns.map_or(connect_start, |(_, end)| end);
+        let tls_start = marks.tls_start.filter(|start| *start >= tcp_start);
+        timings.dns = dns.map(|(start, end)| end.saturating_duration_since(start));
+        timings.connect = Some(
+            tls_start
+                .unwrap_or(connected)
+                .saturating_duration_since(tcp_start),
+        );
+        timings.tls = tls_start.map(|start| connected.saturating_duration_since(start));
+        timings.first_byte = headers.saturating_duration_since(connected.max(sent));
+        timings
+    }
+}
+
+/// Resolves host names with the system resolver, as reqwest does by default, timing each
+/// lookup
+#[derive(Debug, Default)]
+pub struct TimingResolver;
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let start = Instant::now();
+            let addrs = tokio::net::lookup_host(format!("{}:0", name.as_str())).await?;
+            mark(|marks| marks.dns = Some((start, Instant::now())));
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+/// Connector layer timing how long each new connection takes to be ready
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimingLayer;
+
+impl<S> Layer<S> for TimingLayer {
+    type Service = TimingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimingConnector(inner)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimingConnector<S>(S);
+
+impl<S, R> Service<R> for TimingConnector<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+    S::Response: 'static,
+    S::Error: 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let start = Instant::now();
+        let connecting = self.0.call(request);
+        Box::pin(async move {
+            let connection = connecting.await;
+            mark(|marks| marks.connect = Some((start, Instant::now())));
+            connection
+        })
+    }
+}
+
+/// The TLS configuration reqwest uses by default, with key exchange groups that mark when
+/// handshakes start. reqwest keeps the ALPN protocols of a preconfigured config, which offers
+/// none. `insecure` accepts any certificate, like `danger_accept_invalid_certs`.
+pub fn tls_config(insecure: bool) -> Result<rustls::ClientConfig, rustls::Error> {
+    let provider = Arc::new(timed_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+    let config = if insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+            .with_no_client_auth()
+    } else {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        builder.with_root_certificates(roots).with_no_client_auth()
+    };
+
+    Ok(config)
+}
+
+/// The default crypto provider, with each key exchange group wrapped in [`TimedKxGroup`]
+fn timed_provider() -> CryptoProvider {
+    static KX_GROUPS: OnceLock<Vec<TimedKxGroup>> = OnceLock::new();
+
+    let provider = rustls::crypto::ring::default_provider();
+    let groups = KX_GROUPS.get_or_init(|| {
+        provider
+            .kx_groups
+            .iter()
+            .map(|group| TimedKxGroup(*group))
+            .collect()
+    });
+    CryptoProvider {
+        kx_groups: groups
+            .iter()
+            .map(|group| group as &'static dyn SupportedKxGroup)
+            .collect(),
+        ..provider
+    }
+}
+
+/// Key exchange group that marks the start of a TLS handshake, when the client generates the
+/// key share it sends in its ClientHello
+#[derive(Debug)]
+struct TimedKxGroup(&'static dyn SupportedKxGroup);
+
+impl SupportedKxGroup for TimedKxGroup {
+    fn start(&self) -> Result<Box<dyn ActiveKeyExchange>, rustls::Error> {
+        // Later key exchanges in the same handshake, as for TLS 1.2, keep the first mark
+        mark(|marks| {
+            let in_progress = match (marks.tls_start, marks.connect) {
+                (Some(start), Some((_, connected))) => start > connected,
+                (start, None) => start.is_some(),
+                (None, Some(_)) => false,
+            };
+            if !in_progress {
+                marks.tls_start = Some(Instant::now());
+            }
+        });
+        self.0.start()
+    }
+
+    fn start_and_complete(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        self.0.start_and_complete(peer_pub_key)
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        self.0.ffdhe_group()
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.0.name()
+    }
+
+    fn fips(&self) -> bool {
+        self.0.fips()
+    }
+
+    fn usable_for_version(&self, version: ProtocolVersion) -> bool {
+        self.0.usable_for_version(version)
+    }
+}
+
+/// Accepts any server certificate while still checking handshake signatures
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_waterfall_for_new_connection() {
+        let timings = Timings {
+            dns: Some(ms(10)),
+            connect: Some(ms(10)),
+            tls: Some(ms(20)),
+            first_byte: ms(40),
+            download: ms(0),
+        };
+
+        assert_eq!(
+            timings.waterfall(),
+            [
+                "DNS lookup          10.0ms  █████",
+                "TCP connect         10.0ms       █████",
+                "TLS handshake       20.0ms            ██████████",
+                "Waiting (TTFB)      40.0ms                      ████████████████████",
+                "Download             0.0ms                                         █",
+                "Total               80.0ms",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_waterfall_for_reused_connection() {
+        let timings = Timings {
+            first_byte: ms(3),
+            download: ms(1),
+            ..Timings::default()
+        };
+
+        assert_eq!(
+            timings.waterfall(),
+            [
+                "Connection          reused",
+                &format!("Waiting (TTFB)       3.0ms  {}", "█".repeat(30)),
+                &format!(
+                    "Download             1.0ms  {}{}",
+                    " ".repeat(30),
+                    "█".repeat(10)
+                ),
+                "Total                4.0ms",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[rstest]
+    #[case::reused(None, None, None, (None, None, None, 30))]
+    #[case::plain_http(None, Some((0, 10)), None, (None, Some(10), None, 20))]
+    #[case::https(Some((0, 4)), Some((0, 30)), Some(12), (Some(4), Some(8), Some(18), 0))]
+    fn test_timings_from_marks(
+        #[case] dns: Option<(u64, u64)>,
+        #[case] connect: Option<(u64, u64)>,
+        #[case] tls_start: Option<u64>,
+        #[case] expected: (Option<u64>, Option<u64>, Option<u64>, u64),
+    ) {
+        let base = Instant::now();
+        let at = |millis: u64| base + ms(millis);
+        let recorder = Recorder::default();
+        *recorder.marks.lock().expect("Marks lock") = Marks {
+            dns: dns.map(|(start, end)| (at(start), at(end))),
+            connect: connect.map(|(start, end)| (at(start), at(end))),
+            tls_start: tls_start.map(at),
+        };
+
+        let timings = recorder.timings(at(0), at(30), at(35));
+        let (dns, connect, tls, first_byte) = expected;
+
+        assert_eq!(
+            timings,
+            Timings {
+                dns: dns.map(ms),
+                connect: connect.map(ms),
+                tls: tls.map(ms),
+                first_byte: ms(first_byte),
+                download: ms(5),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_marks_are_only_recorded_inside_a_request() {
+        mark(|marks| marks.tls_start = Some(Instant::now()));
+
+        let recorder = Recorder::default();
+        recorder
+            .record(async { mark(|marks| marks.connect = Some((Instant::now(), Instant::now()))) })
+            .await;
+
+        let marks = recorder.marks.lock().expect("Marks lock");
+        assert!(marks.connect.is_some());
+        assert!(marks.tls_start.is_none());
+    }
+}
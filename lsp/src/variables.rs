@@ -0,0 +1,358 @@
+use crate::headers::Headers;
+use crate::multipart::PartContent;
+use crate::parser::{self, HttpRequest};
+use std::collections::HashMap;
+
+/// Parses a file-level variable declaration such as `@baseUrl = https://example.com`
+pub fn parse_variable_declaration(line: &str) -> Option<(String, String)> {
+    let rest = line.trim().strip_prefix('@')?;
+    let (name, value) = rest.split_once('=')?;
+    let name = name.trim();
+
+    if !is_variable_name(name) {
+        return None;
+    }
+
+    Some((name.to_string(), value.trim().to_string()))
+}
+
+/// Whether `name` can be declared with `@name = value`
+pub fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Replaces every `{{name}}` placeholder with its value, leaving unknown names untouched
+pub fn substitute(text: &str, variables: &HashMap<String, String>) -> String {
+    substitute_with(text, |name| variables.get(name).cloned())
+}
+
+/// Replaces every `{{...}}` placeholder for which `lookup` returns a value.
+/// The expression passed to `lookup` is trimmed of surrounding whitespace.
+pub fn substitute_with<F>(text: &str, mut lookup: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open + 2..].find("}}") else {
+            break;
+        };
+        let close = open + 2 + close;
+
+        result.push_str(&rest[..open]);
+        match lookup(rest[open + 2..close].trim()) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[open..close + 2]),
+        }
+        rest = &rest[close + 2..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Returns true if `text` still contains a `{{...}}` placeholder
+pub fn has_placeholders(text: &str) -> bool {
+    first_placeholder(text).is_some()
+}
+
+fn first_placeholder(text: &str) -> Option<&str> {
+    placeholders(text).into_iter().next()
+}
+
+/// A `{{...}}` placeholder found in a piece of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placeholder<'a> {
+    /// Byte offset of the opening `{{`
+    pub start: usize,
+    /// Byte offset just past the closing `}}`
+    pub end: usize,
+    /// The trimmed expression between the braces
+    pub expression: &'a str,
+}
+
+/// Returns the trimmed expression of every `{{...}}` placeholder in `text`
+pub fn placeholders(text: &str) -> Vec<&str> {
+    placeholder_spans(text)
+        .into_iter()
+        .map(|placeholder| placeholder.expression)
+        .collect()
+}
+
+/// Returns every `{{...}}` placeholder in `text` with its position
+pub fn placeholder_spans(text: &str) -> Vec<Placeholder<'_>> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+
+    while let Some(open) = text[offset..].find("{{") {
+        let open = offset + open;
+        let Some(close) = text[open + 2..].find("}}") else {
+            break;
+        };
+        let close = open + 2 + close;
+
+        spans.push(Placeholder {
+            start: open,
+            end: close + 2,
+            expression: text[open + 2..close].trim(),
+        });
+        offset = close + 2;
+    }
+
+    spans
+}
+
+/// Resolves the remaining placeholders of a parsed request and validates the final URL.
+/// Fails on the first error returned by `lookup`, or the first placeholder it cannot resolve.
+pub fn resolve_request<F>(request: &HttpRequest, mut lookup: F) -> Result<HttpRequest, String>
+where
+    F: FnMut(&str) -> Result<Option<String>, String>,
+{
+    let mut resolve = |text: &str| {
+        let mut error = None;
+        let resolved = substitute_with(text, |name| {
+            lookup(name).unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                None
+            })
+        });
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        match first_placeholder(&resolved) {
+            Some(name) => Err(format!("Undefined variable: {}", name)),
+            None => Ok(resolved),
+        }
+    };
+
+    let url = parser::validate_url(&resolve(&request.url)?)?;
+
+    let mut headers = Headers::new();
+    for (name, value) in &request.headers {
+        headers.append(resolve(name)?, resolve(value)?);
+    }
+
+    let body = match &request.body {
+        Some(body) => Some(resolve(body)?),
+        None => None,
+    };
+
+    let mut multipart = request.multipart.clone();
+    if let Some(multipart) = &mut multipart {
+        for part in &mut multipart.parts {
+            let mut headers = Headers::new();
+            for (name, value) in &part.headers {
+                headers.append(resolve(name)?, resolve(value)?);
+            }
+            part.headers = headers;
+
+            if let PartContent::Text(text) = &part.content {
+                part.content = PartContent::Text(resolve(text)?);
+            }
+        }
+    }
+
+    let mut directives = HashMap::new();
+    for (name, value) in &request.directives {
+        let value = match value {
+            Some(value) => Some(resolve(value)?),
+            None => None,
+        };
+        directives.insert(name.clone(), value);
+    }
+
+    Ok(HttpRequest {
+        url,
+        headers,
+        body,
+        multipart,
+        directives,
+        ..request.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("@baseUrl = https://example.com", "baseUrl", "https://example.com")]
+    #[case("@token=abc123", "token", "abc123")]
+    #[case("  @api_key   =   secret value  ", "api_key", "secret value")]
+    #[case("@host-name = localhost:8080", "host-name", "localhost:8080")]
+    #[case("@empty =", "empty", "")]
+    #[case("@query = a=1&b=2", "query", "a=1&b=2")]
+    fn test_parse_variable_declaration(
+        #[case] line: &str,
+        #[case] expected_name: &str,
+        #[case] expected_value: &str,
+    ) {
+        let (name, value) = parse_variable_declaration(line).expect("Expected a declaration");
+        assert_eq!(name, expected_name);
+        assert_eq!(value, expected_value);
+    }
+
+    #[rstest]
+    #[case("baseUrl = https://example.com")]
+    #[case("@ = value")]
+    #[case("@base url = value")]
+    #[case("@baseUrl")]
+    #[case("# @name login")]
+    fn test_parse_variable_declaration_rejects_invalid_lines(#[case] line: &str) {
+        assert!(parse_variable_declaration(line).is_none());
+    }
+
+    #[rstest]
+    #[case("{{host}}/api", "example.com/api")]
+    #[case("{{ host }}/api", "example.com/api")]
+    #[case("Bearer {{token}}", "Bearer abc")]
+    #[case("{{host}}/{{token}}/{{host}}", "example.com/abc/example.com")]
+    #[case("{{unknown}}/api", "{{unknown}}/api")]
+    #[case("no placeholders", "no placeholders")]
+    #[case("{{unterminated", "{{unterminated")]
+    fn test_substitute(#[case] text: &str, #[case] expected: &str) {
+        let mut variables = HashMap::new();
+        variables.insert("host".to_string(), "example.com".to_string());
+        variables.insert("token".to_string(), "abc".to_string());
+
+        assert_eq!(substitute(text, &variables), expected);
+    }
+
+    fn create_test_request(url: &str) -> HttpRequest {
+        let mut headers = Headers::new();
+        headers.insert("Authorization".to_string(), "Bearer {{token}}".to_string());
+
+        HttpRequest {
+            method: "POST".to_string(),
+            url: url.to_string(),
+            headers,
+            body: Some(r#"{"user": "{{user}}"}"#.to_string()),
+            body_file: None,
+            multipart: None,
+            line_number: 0,
+            name: None,
+            title: None,
+            block_lines: 0..1,
+            body_lines: None,
+            directives: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            placeholders("{{a}}/{{ b.response.body.$.id }}/{{$randomInt 1 2}}/{{open"),
+            vec!["a", "b.response.body.$.id", "$randomInt 1 2"]
+        );
+        assert!(placeholders("no placeholders").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_request_substitutes_everywhere() {
+        let request = create_test_request("{{baseUrl}}/users");
+        let mut variables = HashMap::new();
+        variables.insert("baseUrl".to_string(), "https://example.com".to_string());
+        variables.insert("token".to_string(), "abc".to_string());
+        variables.insert("user".to_string(), "jane".to_string());
+
+        let resolved = resolve_request(&request, |name| Ok(variables.get(name).cloned()))
+            .expect("Request should resolve");
+
+        assert_eq!(resolved.url, "https://example.com/users");
+        assert_eq!(
+            resolved.headers.get("Authorization"),
+            Some(&"Bearer abc".to_string())
+        );
+        assert_eq!(resolved.body.as_deref(), Some(r#"{"user": "jane"}"#));
+    }
+
+    #[test]
+    fn test_resolve_request_reports_undefined_variable() {
+        let request = create_test_request("https://example.com/users");
+        let result = resolve_request(&request, |name| {
+            Ok((name == "token").then(|| "abc".to_string()))
+        });
+
+        assert_eq!(result.unwrap_err(), "Undefined variable: user");
+    }
+
+    #[test]
+    fn test_resolve_request_validates_resolved_url() {
+        let request = create_test_request("{{baseUrl}}/users");
+        let result = resolve_request(&request, |name| match name {
+            "baseUrl" => Ok(Some("file:///etc".to_string())),
+            _ => Ok(Some(String::new())),
+        });
+
+        assert!(result.unwrap_err().contains("Unsupported URL scheme"));
+    }
+
+    #[test]
+    fn test_resolve_request_reports_lookup_errors() {
+        let request = create_test_request("https://example.com/users");
+        let result = resolve_request(&request, |name| match name {
+            "token" => Err("$randomInt expects two arguments: min max".to_string()),
+            _ => Ok(None),
+        });
+
+        assert_eq!(
+            result.unwrap_err(),
+            "$randomInt expects two arguments: min max"
+        );
+    }
+
+    #[test]
+    fn test_resolve_request_substitutes_multipart_parts() {
+        let content = r#"POST https://example.com/upload
+Content-Type: multipart/form-data; boundary=b
+
+--b
+Content-Disposition: form-data; name="{{field}}"
+
+{{value}}
+--b--"#;
+        let request = parser::parse_http_file(content).remove(0);
+
+        let resolved = resolve_request(&request, |name| match name {
+            "field" => Ok(Some("title".to_string())),
+            "value" => Ok(Some("Holiday".to_string())),
+            _ => Ok(None),
+        })
+        .expect("Request should resolve");
+
+        let part = &resolved.multipart.expect("Expected multipart body").parts[0];
+        assert_eq!(
+            part.headers.get("content-disposition"),
+            Some(&"form-data; name=\"title\"".to_string())
+        );
+        assert_eq!(part.content, PartContent::Text("Holiday".to_string()));
+    }
+
+    #[test]
+    fn test_placeholder_spans() {
+        let spans = placeholder_spans("{{a}}/x/{{ b.c }}{{open");
+
+        assert_eq!(
+            spans,
+            vec![
+                Placeholder {
+                    start: 0,
+                    end: 5,
+                    expression: "a",
+                },
+                Placeholder {
+                    start: 8,
+                    end: 17,
+                    expression: "b.c",
+                },
+            ]
+        );
+    }
+}